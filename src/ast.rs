@@ -1,10 +1,10 @@
-use std::fmt;
+use std::{cell::Cell, fmt, rc::Rc};
 
-use crate::{token::Token, utils::pad_number};
+use crate::{interner::Symbol, token::Token, utils::pad_number};
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Debug)]
 pub enum Expr {
-    String(String),
+    String(Symbol),
     Number(f64),
     Boolean(bool),
     Nil,
@@ -17,12 +17,66 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
     Grouping(Box<Expr>),
-    Variable(String),
+    // A string with embedded `${...}` expressions, as the literal text and
+    // expressions in source order. Each part is stringified and concatenated.
+    Interpolation(Vec<Expr>),
+    // `slot` is filled in by the resolver for locals and left as `None` for
+    // globals, which are looked up by name.
+    Variable {
+        name: Token,
+        slot: Cell<Option<Slot>>,
+    },
     Assign {
-        name: String,
+        name: Token,
+        value: Box<Expr>,
+        slot: Cell<Option<Slot>>,
+    },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
         value: Box<Expr>,
     },
+    This {
+        keyword: Token,
+        slot: Cell<Option<Slot>>,
+    },
+    Super {
+        keyword: Token,
+        method: Token,
+        slot: Cell<Option<Slot>>,
+    },
+}
+
+// Where a local variable lives: `depth` scopes out from its use, at `index`
+// among the variables declared in that scope.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+impl Expr {
+    pub fn variable(name: Token) -> Self {
+        Expr::Variable {
+            name,
+            slot: Cell::new(None),
+        }
+    }
 }
 
 impl fmt::Display for Expr {
@@ -38,16 +92,87 @@ impl fmt::Display for Expr {
                 operator,
                 right,
             } => write!(fmt, "({} {} {})", operator.lexeme, left, right),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => write!(fmt, "({} {} {})", operator.lexeme, left, right),
             Expr::Grouping(expr) => write!(fmt, "(group {})", expr),
-            Expr::Variable(name) => write!(fmt, "{}", name),
-            Expr::Assign { name, value } => write!(fmt, "{} {}", name, value),
+            Expr::Interpolation(parts) => {
+                write!(fmt, "(interpolate")?;
+                for part in parts {
+                    write!(fmt, " {}", part)?;
+                }
+                write!(fmt, ")")
+            }
+            Expr::Variable { name, .. } => write!(fmt, "{}", name.lexeme),
+            Expr::Assign { name, value, .. } => write!(fmt, "{} {}", name.lexeme, value),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(fmt, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(fmt, " {}", argument)?;
+                }
+                write!(fmt, ")")
+            }
+            Expr::Get { object, name } => write!(fmt, "(. {} {})", object, name.lexeme),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => write!(fmt, "(= {} {} {})", object, name.lexeme, value),
+            Expr::This { .. } => write!(fmt, "this"),
+            Expr::Super { method, .. } => write!(fmt, "(super {})", method.lexeme),
         }
     }
 }
 
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    // Index of the function in its enclosing scope, or `None` at the top
+    // level. Filled in by the resolver, like the `slot` of a declaration.
+    pub slot: Cell<Option<usize>>,
+}
+
+#[derive(Debug)]
 pub enum Stmt {
     Print(Expr),
     Expression(Expr),
-    Var(String, Expr),
+    // Declarations record the index the resolver gave the new variable in
+    // its scope, or `None` for a global.
+    Var {
+        name: Token,
+        initializer: Expr,
+        slot: Cell<Option<usize>>,
+    },
     Block(Vec<Stmt>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    // `increment` is only set for desugared `for` loops, so that `continue`
+    // still runs it before the next iteration.
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+        increment: Option<Expr>,
+    },
+    Break,
+    Continue,
+    Function(Rc<FunctionDecl>),
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+        slot: Cell<Option<usize>>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
+    },
 }
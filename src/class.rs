@@ -0,0 +1,95 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{function::LoxFunction, gc, interner::Symbol, value::Value};
+
+pub struct LoxClass {
+    pub name: Symbol,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: Symbol,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &Symbol) -> Option<Rc<LoxFunction>> {
+        self.methods.get(name).cloned().or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method(&Symbol::intern("init"))
+            .map_or(0, |initializer| initializer.arity())
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: RefCell<HashMap<Symbol, Value>>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Fields shadow methods; methods come back bound to this instance.
+    pub fn get(instance: &Rc<LoxInstance>, name: &Symbol) -> Option<Value> {
+        if let Some(value) = instance.fields.borrow().get(name) {
+            return Some(value.clone());
+        }
+
+        instance
+            .class
+            .find_method(name)
+            .map(|method| Value::Function(gc::alloc(method.bind(Rc::clone(instance)))))
+    }
+
+    pub fn set(&self, name: Symbol, value: Value) {
+        self.fields.borrow_mut().insert(name, value);
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LoxClass")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+// Fields can point back at the instance, so only the class is shown.
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LoxInstance")
+            .field("class", &self.class)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.name)
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} instance", self.class.name)
+    }
+}
@@ -1,5 +1,7 @@
 use crate::error::{ExitCode, LoxError};
 
+pub mod compile;
+pub mod disassemble;
 pub mod evaluate;
 pub mod parse;
 pub mod run;
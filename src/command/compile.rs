@@ -0,0 +1,57 @@
+use std::{fs, path::PathBuf, process};
+
+use super::Command;
+use crate::{
+    error::{ExitCode, LoxError},
+    optimizer::optimize,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    vm::{bytecode::serialize, compiler::Compiler},
+};
+
+pub struct CompileCommand {
+    file_contents: String,
+    output: PathBuf,
+}
+
+impl CompileCommand {
+    pub fn new(file_contents: String, output: PathBuf) -> Self {
+        Self {
+            file_contents,
+            output,
+        }
+    }
+}
+
+impl Command for CompileCommand {
+    fn execute(&self) -> Result<ExitCode, LoxError> {
+        let mut scanner = Scanner::new(self.file_contents.clone());
+        let tokens = scanner.scan_tokens();
+
+        let mut parser = Parser::new(tokens.to_vec(), true);
+        if scanner.has_error() {
+            process::exit(65);
+        }
+
+        let compiled = parser.parse().and_then(|statements| {
+            let statements = optimize(statements);
+            Resolver::new().resolve(&statements)?;
+            Compiler::new().compile(&statements)
+        });
+
+        match compiled {
+            Ok(script) => {
+                if let Err(e) = fs::write(&self.output, serialize(&script)) {
+                    eprintln!("Failed to write {}: {}", self.output.display(), e);
+                    process::exit(74);
+                }
+                process::exit(0)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(65)
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::process;
+
+use super::Command;
+use crate::{
+    error::{ExitCode, LoxError},
+    optimizer::optimize,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    vm::{compiler::Compiler, disassembler::disassemble},
+};
+
+pub struct DisassembleCommand {
+    file_contents: String,
+}
+
+impl DisassembleCommand {
+    pub fn new(file_contents: String) -> Self {
+        Self { file_contents }
+    }
+}
+
+impl Command for DisassembleCommand {
+    fn execute(&self) -> Result<ExitCode, LoxError> {
+        let mut scanner = Scanner::new(self.file_contents.clone());
+        let tokens = scanner.scan_tokens();
+
+        let mut parser = Parser::new(tokens.to_vec(), true);
+        if scanner.has_error() {
+            process::exit(65);
+        }
+
+        let compiled = parser.parse().and_then(|statements| {
+            let statements = optimize(statements);
+            Resolver::new().resolve(&statements)?;
+            Compiler::new().compile(&statements)
+        });
+
+        match compiled {
+            Ok(script) => {
+                print!("{}", disassemble(&script));
+                process::exit(0)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(65)
+            }
+        }
+    }
+}
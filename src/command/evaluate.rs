@@ -29,28 +29,20 @@ impl Command for EvaluateCommand {
         if scanner.has_error() {
             process::exit(65);
         }
-        let statement = match parser.parse() {
+        let statements = match parser.parse() {
             Ok(stmt) => stmt,
             Err(e) => {
-                if let Some(line) = e.line {
-                    eprintln!("[line {}] Error: {}", line, e.message);
-                } else {
-                    eprintln!("Error: {}", e.message);
-                }
+                eprintln!("{}", e);
                 process::exit(65);
             }
         };
         let mut interpreter = Interpreter::new();
-        for statement in statement {
+        for statement in &statements {
             if let Stmt::Expression(expr) = statement {
                 let expr = match interpreter.evaluate(expr) {
                     Ok(value) => value,
                     Err(e) => {
-                        if let Some(line) = e.line {
-                            eprintln!("[line {}] Error: {}", line, e.message);
-                        } else {
-                            eprintln!("Error: {}", e.message);
-                        }
+                        eprintln!("{}", e);
                         process::exit(70);
                     }
                 };
@@ -2,6 +2,7 @@ use super::Command;
 use crate::{
     ast::Stmt,
     error::{ExitCode, LoxError},
+    optimizer::optimize,
     parser::Parser,
     scanner::Scanner,
 };
@@ -9,11 +10,16 @@ use std::process;
 
 pub struct ParseCommand {
     file_contents: String,
+    // Print the tree after constant folding rather than as written.
+    optimized: bool,
 }
 
 impl ParseCommand {
-    pub fn new(file_contents: String) -> Self {
-        Self { file_contents }
+    pub fn new(file_contents: String, optimized: bool) -> Self {
+        Self {
+            file_contents,
+            optimized,
+        }
     }
 
     fn handle_statement(&self, stmt: Stmt) {
@@ -34,18 +40,19 @@ impl Command for ParseCommand {
         }
 
         match parser.parse() {
-            Ok(statement) => {
-                for stmt in statement {
+            Ok(statements) => {
+                let statements = if self.optimized {
+                    optimize(statements)
+                } else {
+                    statements
+                };
+                for stmt in statements {
                     self.handle_statement(stmt);
                 }
                 process::exit(0)
             }
             Err(e) => {
-                if let Some(line) = e.line {
-                    eprintln!("[line {}] Error: {}", line, e.message);
-                } else {
-                    eprintln!("Error: {}", e.message);
-                }
+                eprintln!("{}", e);
                 process::exit(65)
             }
         }
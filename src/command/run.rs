@@ -1,56 +1,140 @@
-use std::process;
+use std::{process, rc::Rc};
 
 use super::Command;
 use crate::{
+    ast::Stmt,
     error::{ExitCode, LoxError},
+    gc,
     interpreter::Interpreter,
+    limits::Limits,
+    optimizer::optimize,
     parser::Parser,
+    resolver::Resolver,
     scanner::Scanner,
+    vm::{bytecode::deserialize, chunk::FunctionProto, compiler::Compiler, Vm},
 };
 
+// What `run` was given: Lox source, or a script written by `compile`.
+enum Program {
+    Source(String),
+    Bytecode(Vec<u8>),
+}
+
 pub struct RunCommand {
-    file_contents: String,
+    program: Program,
+    // Compile to bytecode and run on the VM instead of walking the tree.
+    use_vm: bool,
+    // Report garbage collector activity on stderr once the program finishes.
+    gc_stats: bool,
+    limits: Limits,
 }
 
 impl RunCommand {
-    pub fn new(file_contents: String) -> Self {
-        Self { file_contents }
+    pub fn new(file_contents: String, use_vm: bool, gc_stats: bool) -> Self {
+        Self {
+            program: Program::Source(file_contents),
+            use_vm,
+            gc_stats,
+            limits: Limits::default(),
+        }
     }
-}
 
-impl Command for RunCommand {
-    fn execute(&self) -> Result<ExitCode, LoxError> {
-        let mut scanner = Scanner::new(self.file_contents.clone());
+    // Precompiled scripts always run on the VM.
+    pub fn from_bytecode(bytes: Vec<u8>, gc_stats: bool) -> Self {
+        Self {
+            program: Program::Bytecode(bytes),
+            use_vm: true,
+            gc_stats,
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn run_vm(&self, statements: &[Stmt]) -> Result<(), (LoxError, i32)> {
+        let script = Compiler::new().compile(statements).map_err(|e| (e, 65))?;
+        self.interpret(script).map_err(|e| (e, 70))
+    }
+
+    fn interpret(&self, script: Rc<FunctionProto>) -> Result<(), LoxError> {
+        let mut vm = Vm::new().with_limits(self.limits);
+        let result = vm.interpret(script);
+        if self.gc_stats {
+            eprintln!("{}", vm.gc_stats());
+        }
+        result
+    }
+
+    fn run_bytecode(&self, bytes: &[u8]) -> Result<ExitCode, LoxError> {
+        let result = deserialize(bytes)
+            .map_err(|e| (e, 65))
+            .and_then(|script| self.interpret(script).map_err(|e| (e, 70)));
+
+        match result {
+            Ok(()) => process::exit(0),
+            Err((e, code)) => {
+                eprintln!("{}", e);
+                process::exit(code);
+            }
+        }
+    }
+
+    fn run_source(&self, file_contents: &str) -> Result<ExitCode, LoxError> {
+        let mut scanner = Scanner::new(file_contents.to_string());
         let tokens = scanner.scan_tokens();
 
-        let mut parser = Parser::new(tokens.to_vec(), true);
+        let mut parser = Parser::new(tokens.to_vec(), true).with_limits(self.limits);
         if scanner.has_error() {
             process::exit(65);
         }
 
         match parser.parse() {
             Ok(statements) => {
-                let mut interpreter = Interpreter::new();
-                match interpreter.interpret(statements) {
+                let statements = optimize(statements);
+                if let Err(e) = Resolver::new().resolve(&statements) {
+                    eprintln!("{}", e);
+                    process::exit(65);
+                }
+
+                if self.use_vm {
+                    match self.run_vm(&statements) {
+                        Ok(()) => process::exit(0),
+                        Err((e, code)) => {
+                            eprintln!("{}", e);
+                            process::exit(code);
+                        }
+                    }
+                }
+
+                let mut interpreter = Interpreter::new().with_limits(self.limits);
+                let result = interpreter.interpret(&statements);
+                if self.gc_stats {
+                    eprintln!("{}", gc::stats());
+                }
+                match result {
                     Ok(_) => process::exit(0),
                     Err(e) => {
-                        if let Some(line) = e.line {
-                            eprintln!("[line {}] Error: {}", line, e.message);
-                        } else {
-                            eprintln!("Error: {}", e.message);
-                        }
+                        eprintln!("{}", e);
                         process::exit(70);
                     }
                 }
             }
             Err(e) => {
-                if let Some(line) = e.line {
-                    eprintln!("[line {}] Error: {}", line, e.message);
-                } else {
-                    eprintln!("Error: {}", e.message);
-                }
+                eprintln!("{}", e);
                 process::exit(65);
             }
         }
     }
 }
+
+impl Command for RunCommand {
+    fn execute(&self) -> Result<ExitCode, LoxError> {
+        match &self.program {
+            Program::Source(file_contents) => self.run_source(file_contents),
+            Program::Bytecode(bytes) => self.run_bytecode(bytes),
+        }
+    }
+}
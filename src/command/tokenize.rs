@@ -11,25 +11,33 @@ use super::Command;
 
 pub struct TokenizeCommand {
     file_contents: String,
+    // Follow each token with its line:column and byte range in the source,
+    // and report errors with their column too.
+    spans: bool,
 }
 
 impl TokenizeCommand {
-    pub fn new(file_contents: String) -> Self {
-        Self { file_contents }
+    pub fn new(file_contents: String, spans: bool) -> Self {
+        Self {
+            file_contents,
+            spans,
+        }
     }
 }
 
 impl Command for TokenizeCommand {
     fn execute(&self) -> Result<ExitCode, LoxError> {
-        let mut scanner = Scanner::new(self.file_contents.clone());
+        let mut scanner = Scanner::new(self.file_contents.clone()).with_spans(self.spans);
         let tokens = scanner.scan_tokens();
 
         for token in tokens {
             let token_type = token.token_type.to_string();
-            let lexeme = if token.token_type == TokenType::String {
-                format!("\"{}\"", token.lexeme)
-            } else {
-                token.lexeme.clone()
+            let lexeme = match token.token_type {
+                TokenType::String => format!("\"{}\"", token.lexeme),
+                TokenType::Interpolation => format!("\"{}${{", token.lexeme),
+                TokenType::InterpolationMiddle => format!("{}${{", token.lexeme),
+                TokenType::InterpolationEnd => format!("{}\"", token.lexeme),
+                _ => token.lexeme.to_string(),
             };
 
             let literal_str = match &token.literal {
@@ -40,7 +48,21 @@ impl Command for TokenizeCommand {
                 None => "null".to_string(),
             };
 
-            println!("{} {} {}", token_type, lexeme, literal_str);
+            if self.spans {
+                let span = token.span;
+                println!(
+                    "{} {} {} {}:{} {}..{}",
+                    token_type,
+                    lexeme,
+                    literal_str,
+                    token.line,
+                    span.column,
+                    span.start,
+                    span.end()
+                );
+            } else {
+                println!("{} {} {}", token_type, lexeme, literal_str);
+            }
         }
 
         if scanner.has_error() {
@@ -1,44 +1,84 @@
-use crate::{ast::Expr, error::LoxError};
-use std::collections::HashMap;
+use crate::{ast::Slot, error::LoxError, interner::Symbol, value::Value};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-#[derive(Debug, Clone)]
+// Globals are looked up by name. Every other scope keeps its variables in a
+// `Vec`, at the indices the resolver handed out when it saw the declarations;
+// a slot stays empty until its declaration runs.
+#[derive(Debug)]
 pub struct Environment {
-    pub values: HashMap<String, Expr>,
-    pub parent: Option<Box<Environment>>,
+    pub values: HashMap<Symbol, Value>,
+    pub slots: Vec<Option<Value>>,
+    pub parent: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            slots: Vec::new(),
             parent: None,
         }
     }
 
-    pub fn with_parent(parent: Environment) -> Self {
+    pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Self {
         Self {
             values: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            slots: Vec::new(),
+            parent: Some(parent),
         }
     }
 
-    pub fn define(&mut self, name: String, value: Expr) {
+    pub fn define(&mut self, name: Symbol, value: Value) {
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: &str) -> Option<&Expr> {
-        self.values
-            .get(name)
-            .or_else(|| self.parent.as_deref().and_then(|parent| parent.get(name)))
+    pub fn define_local(&mut self, index: usize, value: Value) {
+        if index >= self.slots.len() {
+            self.slots.resize(index + 1, None);
+        }
+        self.slots[index] = Some(value);
+    }
+
+    pub fn get(&self, name: &Symbol) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get_at(environment: &RefCell<Environment>, slot: Slot) -> Option<Value> {
+        Self::with_ancestor(environment, slot.depth, |scope| {
+            scope.slots.get(slot.index).cloned().flatten()
+        })
+    }
+
+    pub fn assign_at(environment: &RefCell<Environment>, slot: Slot, value: Value) {
+        Self::with_ancestor(environment, slot.depth, |scope| {
+            scope.define_local(slot.index, value)
+        })
+    }
+
+    // Borrows the enclosing scopes on the way out instead of cloning their
+    // `Rc`s, since this runs on every local variable access.
+    fn with_ancestor<R>(
+        environment: &RefCell<Environment>,
+        depth: usize,
+        f: impl FnOnce(&mut Environment) -> R,
+    ) -> R {
+        if depth == 0 {
+            return f(&mut environment.borrow_mut());
+        }
+
+        let environment = environment.borrow();
+        let parent = environment
+            .parent
+            .as_ref()
+            .unwrap_or_else(|| panic!("Resolved scope depth exceeds the environment chain."));
+        Self::with_ancestor(parent, depth - 1, f)
     }
 
     #[allow(clippy::map_entry)]
-    pub fn assign(&mut self, name: String, value: Expr, line: usize) -> Result<(), LoxError> {
+    pub fn assign(&mut self, name: Symbol, value: Value, line: usize) -> Result<(), LoxError> {
         if self.values.contains_key(&name) {
             self.values.insert(name, value);
             Ok(())
-        } else if let Some(parent) = &mut self.parent {
-            parent.assign(name, value, line)
         } else {
             Err(LoxError::new("Undefined variable", Some(line)))
         }
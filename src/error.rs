@@ -1,9 +1,13 @@
 use std::fmt;
 
+use crate::token::{Span, Token};
+
 #[derive(Debug)]
 pub struct LoxError {
     pub message: String,
     pub line: Option<usize>,
+    // The offending source text, when the error can point at it.
+    pub span: Option<Span>,
 }
 
 impl LoxError {
@@ -11,16 +15,31 @@ impl LoxError {
         LoxError {
             message: message.to_string(),
             line,
+            span: None,
         }
     }
+
+    // An error about a specific token, located by its line and span.
+    pub fn at(token: &Token, message: &str) -> Self {
+        LoxError::new(message, Some(token.line)).with_span(token.span)
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
 }
 
+// The alternate form, `{:#}`, also gives the column the error starts at when
+// it has a span.
 impl fmt::Display for LoxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(line) = self.line {
-            write!(f, "[line {}] Error: {}", line, self.message)
-        } else {
-            write!(f, "Error: {}", self.message)
+        match (self.line, self.span) {
+            (Some(line), Some(span)) if f.alternate() => {
+                write!(f, "[line {}:{}] Error: {}", line, span.column, self.message)
+            }
+            (Some(line), _) => write!(f, "[line {}] Error: {}", line, self.message),
+            (None, _) => write!(f, "Error: {}", self.message),
         }
     }
 }
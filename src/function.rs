@@ -0,0 +1,54 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{ast::FunctionDecl, class::LoxInstance, environment::Environment, gc, value::Value};
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    // Returns a copy of this method whose closure defines `this`.
+    pub fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+        let mut environment = Environment::with_parent(Rc::clone(&self.closure));
+        environment.define_local(0, Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            gc::alloc(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+// The closure usually contains the function itself, so it is left out to
+// keep `Debug` from recursing forever.
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LoxFunction")
+            .field("declaration", &self.declaration)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
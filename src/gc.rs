@@ -0,0 +1,335 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    class::{LoxClass, LoxInstance},
+    environment::Environment,
+    function::LoxFunction,
+    interner::Symbol,
+    value::Value,
+    vm::heap::GcStats,
+};
+
+// Collect for the first time once this many bytes have been allocated, then
+// whenever the heap has grown to `HEAP_GROW_FACTOR` times what survived the
+// last collection. The same policy as the VM's heap.
+const INITIAL_THRESHOLD: usize = 1024 * 1024;
+const HEAP_GROW_FACTOR: usize = 2;
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::new(Collector::new());
+}
+
+// The tree-walker keeps its objects in `Rc`s, which free everything except
+// cycles: an instance stored in its own field, or a closure stored in the
+// scope it captures. Every environment, function, class and instance is
+// allocated through `alloc`, and the collector periodically finds the ones
+// that are only kept alive by each other and breaks their cycles.
+//
+// There is no root set to maintain. An object whose strong count is higher
+// than the number of references to it from other tracked objects is held from
+// outside the object graph: by the interpreter's globals and current scope,
+// or by the scopes, callees and temporaries that calls further up the Rust
+// stack are holding on to. Those are the roots, and anything reachable from
+// them is live.
+pub fn alloc<T: Trace>(object: T) -> Rc<T> {
+    let object = Rc::new(object);
+    COLLECTOR.with(|collector| {
+        let should_collect = {
+            let mut collector = collector.borrow_mut();
+            collector.track(T::track(&object), object.size());
+            collector.should_collect()
+        };
+        if should_collect {
+            // Freeing garbage runs arbitrary drops, so take it out of the
+            // collector before letting it go.
+            let garbage = collector.borrow_mut().collect();
+            drop(garbage);
+        }
+    });
+    object
+}
+
+pub fn stats() -> GcStats {
+    COLLECTOR.with(|collector| collector.borrow().stats)
+}
+
+// Anything that can be allocated through `alloc`.
+pub trait Trace: Sized {
+    fn track(object: &Rc<Self>) -> Tracked;
+
+    fn size(&self) -> usize;
+}
+
+impl Trace for RefCell<Environment> {
+    fn track(object: &Rc<Self>) -> Tracked {
+        Tracked::Environment(Rc::downgrade(object))
+    }
+
+    fn size(&self) -> usize {
+        let owned = self.try_borrow().map_or(0, |environment| {
+            environment.values.capacity() * mem::size_of::<(Symbol, Value)>()
+                + environment.slots.capacity() * mem::size_of::<Option<Value>>()
+        });
+        mem::size_of::<Self>() + owned
+    }
+}
+
+impl Trace for LoxFunction {
+    fn track(object: &Rc<Self>) -> Tracked {
+        Tracked::Function(Rc::downgrade(object))
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+}
+
+impl Trace for LoxClass {
+    fn track(object: &Rc<Self>) -> Tracked {
+        Tracked::Class(Rc::downgrade(object))
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.methods.capacity() * mem::size_of::<(Symbol, Rc<LoxFunction>)>()
+    }
+}
+
+impl Trace for LoxInstance {
+    fn track(object: &Rc<Self>) -> Tracked {
+        Tracked::Instance(Rc::downgrade(object))
+    }
+
+    fn size(&self) -> usize {
+        let owned = self.fields.try_borrow().map_or(0, |fields| {
+            fields.capacity() * mem::size_of::<(Symbol, Value)>()
+        });
+        mem::size_of::<Self>() + owned
+    }
+}
+
+pub enum Tracked {
+    Environment(Weak<RefCell<Environment>>),
+    Function(Weak<LoxFunction>),
+    Class(Weak<LoxClass>),
+    Instance(Weak<LoxInstance>),
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::Environment(weak) => weak.upgrade().map(Object::Environment),
+            Tracked::Function(weak) => weak.upgrade().map(Object::Function),
+            Tracked::Class(weak) => weak.upgrade().map(Object::Class),
+            Tracked::Instance(weak) => weak.upgrade().map(Object::Instance),
+        }
+    }
+}
+
+// A tracked object the collector is holding on to while it runs.
+enum Object {
+    Environment(Rc<RefCell<Environment>>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<LoxInstance>),
+}
+
+impl Object {
+    fn id(&self) -> usize {
+        match self {
+            Object::Environment(rc) => id(rc),
+            Object::Function(rc) => id(rc),
+            Object::Class(rc) => id(rc),
+            Object::Instance(rc) => id(rc),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Environment(rc) => Rc::strong_count(rc),
+            Object::Function(rc) => Rc::strong_count(rc),
+            Object::Class(rc) => Rc::strong_count(rc),
+            Object::Instance(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Object::Environment(rc) => rc.size(),
+            Object::Function(rc) => rc.size(),
+            Object::Class(rc) => rc.size(),
+            Object::Instance(rc) => rc.size(),
+        }
+    }
+
+    // Calls `f` with the id of every tracked object this one holds a strong
+    // reference to, once per reference. Returns false if the object is
+    // borrowed by the running program and can't be looked into.
+    fn references(&self, mut f: impl FnMut(usize)) -> bool {
+        match self {
+            Object::Environment(environment) => {
+                let Ok(environment) = environment.try_borrow() else {
+                    return false;
+                };
+                let values = environment.values.values();
+                values
+                    .chain(environment.slots.iter().flatten())
+                    .filter_map(value_id)
+                    .for_each(&mut f);
+                if let Some(parent) = &environment.parent {
+                    f(id(parent));
+                }
+            }
+            Object::Function(function) => f(id(&function.closure)),
+            Object::Class(class) => {
+                if let Some(superclass) = &class.superclass {
+                    f(id(superclass));
+                }
+                class.methods.values().map(id).for_each(f);
+            }
+            Object::Instance(instance) => {
+                f(id(&instance.class));
+                let Ok(fields) = instance.fields.try_borrow() else {
+                    return false;
+                };
+                fields.values().filter_map(value_id).for_each(f);
+            }
+        }
+        true
+    }
+
+    // Empties a garbage object so the cycles through it come apart, handing
+    // back what it held so it can be dropped once the collector is done.
+    // Every cycle passes through an environment or an instance.
+    fn clear(&self, garbage: &mut Vec<Value>) {
+        match self {
+            Object::Environment(environment) => {
+                let mut environment = environment.borrow_mut();
+                garbage.extend(mem::take(&mut environment.values).into_values());
+                garbage.extend(mem::take(&mut environment.slots).into_iter().flatten());
+                environment.parent = None;
+            }
+            Object::Instance(instance) => {
+                garbage.extend(mem::take(&mut *instance.fields.borrow_mut()).into_values());
+            }
+            Object::Function(_) | Object::Class(_) => {}
+        }
+    }
+}
+
+fn id<T>(object: &Rc<T>) -> usize {
+    Rc::as_ptr(object) as *const () as usize
+}
+
+fn value_id(value: &Value) -> Option<usize> {
+    match value {
+        Value::Function(function) => Some(id(function)),
+        Value::Class(class) => Some(id(class)),
+        Value::Instance(instance) => Some(id(instance)),
+        _ => None,
+    }
+}
+
+struct Collector {
+    objects: Vec<Tracked>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    stats: GcStats,
+}
+
+impl Collector {
+    fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+
+    fn track(&mut self, object: Tracked, size: usize) {
+        self.objects.push(object);
+        self.bytes_allocated += size;
+        self.stats.peak_heap = self.stats.peak_heap.max(self.bytes_allocated);
+    }
+
+    fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_gc
+    }
+
+    // Returns the objects found to be garbage, already emptied, along with
+    // everything they held.
+    fn collect(&mut self) -> (Vec<Object>, Vec<Value>) {
+        let objects: Vec<Object> = self.objects.iter().filter_map(Tracked::upgrade).collect();
+        let index: HashMap<usize, usize> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.id(), i))
+            .collect();
+
+        // Start from each object's strong count, less the one the collector
+        // itself holds, and take away every reference from another tracked
+        // object. Whatever is left over comes from outside.
+        let mut external: Vec<usize> = objects.iter().map(|o| o.strong_count() - 1).collect();
+        let mut opaque = vec![false; objects.len()];
+        for (i, object) in objects.iter().enumerate() {
+            opaque[i] = !object.references(|id| {
+                if let Some(&j) = index.get(&id) {
+                    external[j] = external[j].saturating_sub(1);
+                }
+            });
+        }
+
+        let mut live = vec![false; objects.len()];
+        let mut gray: Vec<usize> = (0..objects.len())
+            .filter(|&i| external[i] > 0 || opaque[i])
+            .collect();
+        for &i in &gray {
+            live[i] = true;
+        }
+        while let Some(i) = gray.pop() {
+            objects[i].references(|id| {
+                if let Some(&j) = index.get(&id) {
+                    if !live[j] {
+                        live[j] = true;
+                        gray.push(j);
+                    }
+                }
+            });
+        }
+
+        let mut garbage = Vec::new();
+        let mut contents = Vec::new();
+        let mut live_bytes = 0;
+        self.objects.clear();
+        for (i, object) in objects.into_iter().enumerate() {
+            if live[i] {
+                live_bytes += object.size();
+                self.objects.push(tracked(&object));
+            } else {
+                object.clear(&mut contents);
+                garbage.push(object);
+            }
+        }
+
+        self.stats.bytes_freed += self.bytes_allocated.saturating_sub(live_bytes);
+        self.bytes_allocated = live_bytes;
+        self.next_gc = (live_bytes * HEAP_GROW_FACTOR).max(INITIAL_THRESHOLD);
+        self.stats.collections += 1;
+        (garbage, contents)
+    }
+}
+
+fn tracked(object: &Object) -> Tracked {
+    match object {
+        Object::Environment(rc) => Trace::track(rc),
+        Object::Function(rc) => Trace::track(rc),
+        Object::Class(rc) => Trace::track(rc),
+        Object::Instance(rc) => Trace::track(rc),
+    }
+}
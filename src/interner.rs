@@ -0,0 +1,102 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+// Drop strings nothing else refers to once the table has grown past this
+// many entries, so programs building many unique strings don't leak them.
+const INITIAL_PURGE_THRESHOLD: usize = 1024;
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
+
+struct Interner {
+    strings: HashSet<Rc<str>>,
+    purge_threshold: usize,
+}
+
+impl Interner {
+    fn new() -> Self {
+        Self {
+            strings: HashSet::new(),
+            purge_threshold: INITIAL_PURGE_THRESHOLD,
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return Rc::clone(existing);
+        }
+
+        if self.strings.len() >= self.purge_threshold {
+            self.strings.retain(|string| Rc::strong_count(string) > 1);
+            self.purge_threshold = (self.strings.len() * 2).max(INITIAL_PURGE_THRESHOLD);
+        }
+
+        let string: Rc<str> = Rc::from(s);
+        self.strings.insert(Rc::clone(&string));
+        string
+    }
+}
+
+// An interned string. Equal contents always share one allocation, so
+// comparing and hashing a symbol only looks at the pointer.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Self {
+        INTERNER.with(|interner| Symbol(interner.borrow_mut().intern(s)))
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).cast::<u8>().hash(state);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?}", &*self.0)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", &*self.0)
+    }
+}
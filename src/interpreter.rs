@@ -1,82 +1,338 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
 use crate::{
-    ast::{Expr, Stmt},
+    ast::{Expr, Slot, Stmt},
+    class::{LoxClass, LoxInstance},
     environment::Environment,
     error::LoxError,
-    token::TokenType,
+    function::LoxFunction,
+    gc,
+    interner::Symbol,
+    limits::Limits,
+    native::{self, NativeFn, NativeFunction},
+    token::{Token, TokenType},
+    value::Value,
 };
 
+// Anything that stops a statement from completing normally: a runtime error,
+// a `return` unwinding to the enclosing call, or a `break`/`continue`
+// unwinding to the enclosing loop.
+enum Unwind {
+    Error(LoxError),
+    Return(Value, usize),
+    Break,
+    Continue,
+}
+
+impl From<LoxError> for Unwind {
+    fn from(error: LoxError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
 pub struct Interpreter {
-    environment: Environment,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Calls in progress, and how many there may be before a stack overflow.
+    calls: usize,
+    max_call_depth: usize,
+    // Where the native stack stood when the interpreter was created, and how
+    // much past that its calls may use.
+    stack_base: usize,
+    stack_budget: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        Self {
-            environment: Environment::new(),
-        }
+        let globals = gc::alloc(RefCell::new(Environment::new()));
+        let limits = Limits::default();
+        let mut interpreter = Self {
+            environment: Rc::clone(&globals),
+            globals,
+            calls: 0,
+            max_call_depth: limits.max_call_depth,
+            stack_base: stack_address(),
+            stack_budget: limits.call_stack_budget(),
+        };
+        interpreter.define_native("clock", 0, native::clock);
+        interpreter
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_call_depth = limits.max_call_depth;
+        self.stack_budget = limits.call_stack_budget();
+        self
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), Value::NativeFunction(Rc::new(native)));
     }
 
-    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
         for stmt in statements {
-            self.execute(stmt)?;
+            match self.execute(stmt) {
+                Ok(()) => {}
+                Err(Unwind::Error(e)) => return Err(e),
+                Err(Unwind::Return(_, line)) => {
+                    return Err(LoxError::new(
+                        "Can't return from top-level code",
+                        Some(line),
+                    ))
+                }
+                Err(Unwind::Break | Unwind::Continue) => {
+                    unreachable!("the parser rejects 'break' and 'continue' outside of loops")
+                }
+            }
         }
         Ok(())
     }
 
-    fn execute(&mut self, stmt: Stmt) -> Result<(), LoxError> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         match stmt {
             Stmt::Print(expr) => {
                 let value = self.evaluate(expr)?;
                 self.print_value(value);
             }
-            Stmt::Var(name, initializer) => {
+            Stmt::Var {
+                name,
+                initializer,
+                slot,
+            } => {
                 let value = self.evaluate(initializer)?;
-                self.environment.define(name, value);
+                self.declare(name.lexeme.clone(), slot.get(), value);
             }
             Stmt::Expression(expr) => {
                 self.evaluate(expr)?;
             }
-            Stmt::Block(statements) => self.execute_block(statements)?,
+            Stmt::Block(statements) => {
+                let environment = Environment::with_parent(Rc::clone(&self.environment));
+                self.execute_block(statements, environment)?
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.evaluate(condition)?;
+                if condition.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => loop {
+                let value = self.evaluate(condition)?;
+                if !value.is_truthy() {
+                    break;
+                }
+                match self.execute(body) {
+                    Ok(()) | Err(Unwind::Continue) => {}
+                    Err(Unwind::Break) => break,
+                    Err(unwind) => return Err(unwind),
+                }
+                if let Some(increment) = increment {
+                    self.evaluate(increment)?;
+                }
+            },
+            Stmt::Break => return Err(Unwind::Break),
+            Stmt::Continue => return Err(Unwind::Continue),
+            Stmt::Function(declaration) => {
+                let name = declaration.name.lexeme.clone();
+                let slot = declaration.slot.get();
+                let function =
+                    LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+                self.declare(name, slot, Value::Function(gc::alloc(function)));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                slot,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass) => match self.evaluate(superclass)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(LoxError::new(
+                                "Superclass must be a class",
+                                Some(name.line),
+                            )
+                            .into())
+                        }
+                    },
+                    None => None,
+                };
+
+                // Methods of a subclass close over an extra scope holding `super`.
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut environment =
+                            Environment::with_parent(Rc::clone(&self.environment));
+                        environment.define_local(0, Value::Class(Rc::clone(superclass)));
+                        gc::alloc(RefCell::new(environment))
+                    }
+                    None => Rc::clone(&self.environment),
+                };
+
+                let methods = methods
+                    .iter()
+                    .map(|declaration| {
+                        let is_initializer = declaration.name.lexeme == "init";
+                        let method = LoxFunction::new(
+                            Rc::clone(declaration),
+                            Rc::clone(&closure),
+                            is_initializer,
+                        );
+                        (declaration.name.lexeme.clone(), gc::alloc(method))
+                    })
+                    .collect();
+                let class = LoxClass::new(name.lexeme.clone(), superclass, methods);
+                self.declare(
+                    name.lexeme.clone(),
+                    slot.get(),
+                    Value::Class(gc::alloc(class)),
+                );
+            }
+            Stmt::Return { keyword, value } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value, keyword.line));
+            }
         }
         Ok(())
     }
 
-    fn execute_block(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
-        let previous_environment = self.environment.clone();
-        self.environment = Environment::with_parent(previous_environment);
+    // Binds a newly declared variable in the current scope: in the slot the
+    // resolver gave it, or by name when it is a global.
+    fn declare(&mut self, name: Symbol, slot: Option<usize>, value: Value) {
+        match slot {
+            Some(index) => self.environment.borrow_mut().define_local(index, value),
+            None => self.environment.borrow_mut().define(name, value),
+        }
+    }
 
-        for stmt in statements {
-            self.execute(stmt)?;
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let previous =
+            std::mem::replace(&mut self.environment, gc::alloc(RefCell::new(environment)));
+
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+
+        self.environment = previous;
+        result
+    }
+
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<Value>,
+        line: usize,
+    ) -> Result<Value, LoxError> {
+        // Nesting within a single call is bounded by the parser, so only calls
+        // can keep the stack growing. A call nesting deeply can run short of
+        // native stack before the call limit, which is reported the same way.
+        let stack_used = self.stack_base.abs_diff(stack_address());
+        if self.calls == self.max_call_depth || stack_used > self.stack_budget {
+            return Err(LoxError::new("Stack overflow", Some(line)));
         }
 
-        self.environment = *self
-            .environment
-            .parent
-            .clone()
-            .unwrap_or_else(|| panic!("Parent environment was expected but not found."));
+        let mut environment = Environment::with_parent(Rc::clone(&function.closure));
+        for (index, argument) in arguments.into_iter().enumerate() {
+            environment.define_local(index, argument);
+        }
 
-        Ok(())
+        self.calls += 1;
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.calls -= 1;
+        let value = match result {
+            Ok(()) => Value::Nil,
+            Err(Unwind::Return(value, _)) => value,
+            Err(Unwind::Error(e)) => return Err(e),
+            Err(Unwind::Break | Unwind::Continue) => {
+                unreachable!("the parser rejects 'break' and 'continue' outside of loops")
+            }
+        };
+
+        if function.is_initializer {
+            let this = Slot { depth: 0, index: 0 };
+            return Ok(Environment::get_at(&function.closure, this).unwrap_or(Value::Nil));
+        }
+        Ok(value)
     }
 
-    pub fn evaluate(&mut self, expr: Expr) -> Result<Expr, LoxError> {
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        line: usize,
+    ) -> Result<Value, LoxError> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::NativeFunction(function) => function.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(LoxError::new(
+                    "Can only call functions and classes",
+                    Some(line),
+                ))
+            }
+        };
+        if arguments.len() != arity {
+            return Err(LoxError::new(
+                &format!("Expected {} arguments but got {}", arity, arguments.len()),
+                Some(line),
+            ));
+        }
+        match callee {
+            Value::Class(class) => {
+                let instance = gc::alloc(LoxInstance::new(Rc::clone(&class)));
+                if let Some(initializer) = class.find_method(&Symbol::intern("init")) {
+                    self.call_function(&initializer.bind(Rc::clone(&instance)), arguments, line)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            Value::Function(function) => self.call_function(&function, arguments, line),
+            Value::NativeFunction(function) => function
+                .call(&arguments)
+                .map_err(|e| LoxError::new(&e.message, e.line.or(Some(line)))),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, LoxError> {
         match expr {
-            Expr::String(s) => Ok(Expr::String(s)),
-            Expr::Number(n) => Ok(Expr::Number(n)),
-            Expr::Boolean(b) => Ok(Expr::Boolean(b)),
-            Expr::Nil => Ok(Expr::Nil),
+            Expr::String(s) => Ok(Value::String(s.clone())),
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Boolean(b) => Ok(Value::Boolean(*b)),
+            Expr::Nil => Ok(Value::Nil),
             Expr::Unary { operator, right } => {
-                let right_val = self.evaluate(*right)?;
+                let right_val = self.evaluate(right)?;
                 let line = operator.line;
                 match operator.token_type {
                     TokenType::Minus => {
-                        if let Expr::Number(n) = right_val {
-                            return Ok(Expr::Number(-n));
+                        if let Value::Number(n) = right_val {
+                            return Ok(Value::Number(-n));
                         }
                         Err(LoxError::new("Operand must be a number", Some(line)))
                     }
                     TokenType::Bang => {
-                        let is_truthy = self.is_truthy(&right_val);
-                        Ok(Expr::Boolean(!is_truthy))
+                        let is_truthy = right_val.is_truthy();
+                        Ok(Value::Boolean(!is_truthy))
                     }
                     _ => Err(LoxError::new("Unknown unary operator", Some(line))),
                 }
@@ -86,55 +342,133 @@ impl Interpreter {
                 operator,
                 right,
             } => {
-                let left_val = self.evaluate(*left)?;
-                let right_val = self.evaluate(*right)?;
+                let left_val = self.evaluate(left)?;
+                let right_val = self.evaluate(right)?;
                 let line = operator.line;
                 self.handle_binary_op(left_val, &operator.token_type, right_val, line)
             }
-            Expr::Grouping(inner_expr) => self.evaluate(*inner_expr),
-            Expr::Assign { name, value } => {
-                let evaluated_value = self.evaluate(*value)?;
-                let key = name.clone();
-                self.environment.assign(key, evaluated_value, 0)?;
-                Ok(self.environment.get(&name).unwrap().clone())
-            }
-            Expr::Variable(name) => {
-                if let Some(value) = self.environment.get(&name) {
-                    Ok(value.clone())
-                } else {
-                    Err(LoxError::new("Undefined variable", None))
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left_val = self.evaluate(left)?;
+                let is_truthy = left_val.is_truthy();
+                match operator.token_type {
+                    TokenType::Or if is_truthy => Ok(left_val),
+                    TokenType::And if !is_truthy => Ok(left_val),
+                    _ => self.evaluate(right),
                 }
             }
-        }
-    }
+            Expr::Grouping(inner_expr) => self.evaluate(inner_expr),
+            Expr::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    string.push_str(&self.evaluate(part)?.to_string());
+                }
+                Ok(Value::String(Symbol::intern(&string)))
+            }
+            Expr::Assign { name, value, slot } => {
+                let evaluated_value = self.evaluate(value)?;
+                match slot.get() {
+                    Some(slot) => {
+                        Environment::assign_at(&self.environment, slot, evaluated_value.clone())
+                    }
+                    None => self.globals.borrow_mut().assign(
+                        name.lexeme.clone(),
+                        evaluated_value.clone(),
+                        name.line,
+                    )?,
+                }
+                Ok(evaluated_value)
+            }
+            Expr::Variable { name, slot } => self.look_up_variable(name, slot),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
 
-    pub fn print_value(&self, value: Expr) {
-        match value {
-            Expr::String(s) => println!("{}", s),
-            Expr::Number(n) => println!("{}", n),
-            Expr::Boolean(b) => println!("{}", b),
-            Expr::Nil => println!("nil"),
-            Expr::Unary { operator, right } => {
-                println!("({} {})", operator.lexeme, right)
+                self.call_value(callee, arguments, paren.line)
+            }
+            Expr::Get { object, name } => {
+                let Value::Instance(instance) = self.evaluate(object)? else {
+                    return Err(LoxError::new(
+                        "Only instances have properties",
+                        Some(name.line),
+                    ));
+                };
+                LoxInstance::get(&instance, &name.lexeme).ok_or_else(|| {
+                    LoxError::new(
+                        &format!("Undefined property '{}'", name.lexeme),
+                        Some(name.line),
+                    )
+                })
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let Value::Instance(instance) = self.evaluate(object)? else {
+                    return Err(LoxError::new("Only instances have fields", Some(name.line)));
+                };
+                let value = self.evaluate(value)?;
+                instance.set(name.lexeme.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::This { keyword, slot } => self.look_up_variable(keyword, slot),
+            Expr::Super { method, slot, .. } => {
+                // The resolver places `this` in the scope just inside `super`.
+                let slot = slot
+                    .get()
+                    .unwrap_or_else(|| panic!("'super' was expected to be resolved."));
+                let superclass = Environment::get_at(&self.environment, slot);
+                let this = Slot {
+                    depth: slot.depth - 1,
+                    index: 0,
+                };
+                let instance = Environment::get_at(&self.environment, this);
+                let (Some(Value::Class(superclass)), Some(Value::Instance(instance))) =
+                    (superclass, instance)
+                else {
+                    panic!("'super' and 'this' were expected in the enclosing scopes.");
+                };
+                let method_fn = superclass.find_method(&method.lexeme).ok_or_else(|| {
+                    LoxError::new(
+                        &format!("Undefined property '{}'", method.lexeme),
+                        Some(method.line),
+                    )
+                })?;
+                Ok(Value::Function(gc::alloc(method_fn.bind(instance))))
             }
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => println!("({} {} {})", operator.lexeme, left, right),
-            Expr::Grouping(expr) => println!("(group {})", expr),
-            Expr::Variable(name) => println!("{}", name),
-            Expr::Assign { name, value } => println!("{} {}", name, value),
         }
     }
 
+    fn look_up_variable(&self, name: &Token, slot: &Cell<Option<Slot>>) -> Result<Value, LoxError> {
+        let value = match slot.get() {
+            Some(slot) => Environment::get_at(&self.environment, slot),
+            None => self.globals.borrow().get(&name.lexeme),
+        };
+        value.ok_or_else(|| LoxError::new("Undefined variable", Some(name.line)))
+    }
+
+    pub fn print_value(&self, value: Value) {
+        println!("{}", value);
+    }
+
     fn handle_binary_op(
         &self,
-        left: Expr,
+        left: Value,
         operator: &TokenType,
-        right: Expr,
+        right: Value,
         line: usize,
-    ) -> Result<Expr, LoxError> {
+    ) -> Result<Value, LoxError> {
         match operator {
             TokenType::Plus => self.handle_plus(left, right, line),
             TokenType::Minus => self.handle_minus(left, right, line),
@@ -146,14 +480,16 @@ impl Interpreter {
             TokenType::LessEqual => self.handle_less_equal(left, right, line),
             TokenType::EqualEqual => self.handle_equal_equal(left, right),
             TokenType::BangEqual => self.handle_bang_equal(left, right),
-            _ => Ok(Expr::Nil),
+            _ => Ok(Value::Nil),
         }
     }
 
-    fn handle_plus(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
+    fn handle_plus(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
         match (left, right) {
-            (Expr::Number(l), Expr::Number(r)) => Ok(Expr::Number(l + r)),
-            (Expr::String(l), Expr::String(r)) => Ok(Expr::String(format!("{}{}", l, r))),
+            (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+            (Value::String(l), Value::String(r)) => {
+                Ok(Value::String(Symbol::intern(&format!("{}{}", l, r))))
+            }
             _ => Err(LoxError::new(
                 "Operands must be two numbers or two strings",
                 Some(line),
@@ -161,78 +497,81 @@ impl Interpreter {
         }
     }
 
-    fn handle_minus(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Number(l - r))
+    fn handle_minus(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Number(l - r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_divide(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
+    fn handle_divide(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
             if r == 0.0 {
                 return Err(LoxError::new("Division by zero", Some(line)));
             }
-            Ok(Expr::Number(l / r))
+            Ok(Value::Number(l / r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_multiply(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Number(l * r))
+    fn handle_multiply(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Number(l * r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_greater(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Boolean(l > r))
+    fn handle_greater(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Boolean(l > r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_greater_equal(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Boolean(l >= r))
+    fn handle_greater_equal(
+        &self,
+        left: Value,
+        right: Value,
+        line: usize,
+    ) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Boolean(l >= r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_less(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Boolean(l < r))
+    fn handle_less(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Boolean(l < r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_less_equal(&self, left: Expr, right: Expr, line: usize) -> Result<Expr, LoxError> {
-        if let (Expr::Number(l), Expr::Number(r)) = (left, right) {
-            Ok(Expr::Boolean(l <= r))
+    fn handle_less_equal(&self, left: Value, right: Value, line: usize) -> Result<Value, LoxError> {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            Ok(Value::Boolean(l <= r))
         } else {
             Err(LoxError::new("Operands must be numbers.", Some(line)))
         }
     }
 
-    fn handle_equal_equal(&self, left: Expr, right: Expr) -> Result<Expr, LoxError> {
-        Ok(Expr::Boolean(left == right))
+    fn handle_equal_equal(&self, left: Value, right: Value) -> Result<Value, LoxError> {
+        Ok(Value::Boolean(left == right))
     }
 
-    fn handle_bang_equal(&self, left: Expr, right: Expr) -> Result<Expr, LoxError> {
-        Ok(Expr::Boolean(left != right))
+    fn handle_bang_equal(&self, left: Value, right: Value) -> Result<Value, LoxError> {
+        Ok(Value::Boolean(left != right))
     }
+}
 
-    fn is_truthy(&self, value: &Expr) -> bool {
-        match value {
-            Expr::Boolean(b) => *b,
-            Expr::Nil => false,
-            _ => true,
-        }
-    }
+// The address of a local, which tells how deep the native stack currently is.
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
 }
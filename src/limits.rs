@@ -0,0 +1,57 @@
+// How deeply a program may nest and recurse. The parser rejects source nested
+// past `max_depth`, and both backends report a stack overflow once
+// `max_call_depth` calls are in progress, so a program recurses exactly as far
+// on the VM as it does in the tree-walker.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // Parentheses, unary operands, assignment values and nested statements.
+    pub max_depth: usize,
+    // Function calls and initializers running at once, not counting the
+    // script itself.
+    pub max_call_depth: usize,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 4096;
+
+// The parser, the passes over its tree and the tree-walker all recurse, and
+// the default limits are sized for this stack. At those limits an optimized
+// build needs at most 17 MiB: a chain of 5,000 operators takes 5 MiB to
+// optimize, resolve and compile, 1,000 nested parentheses take 6 MiB to parse,
+// 4,096 calls of a small recursive function take 11 MiB in the tree-walker,
+// and one call's worth of nesting at most 6 MiB more. Unoptimized builds give
+// every temporary its own stack slot and need about ten times as much.
+const STACK_SIZE: usize = if cfg!(debug_assertions) { 256 } else { 32 } * 1024 * 1024;
+const NESTING_STACK_SIZE: usize = if cfg!(debug_assertions) { 64 } else { 6 } * 1024 * 1024;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+        }
+    }
+}
+
+impl Limits {
+    // The stack the interpreter thread needs. Raising either limit grows the
+    // default stack in proportion, since each level costs about the same.
+    pub fn stack_size(&self) -> usize {
+        STACK_SIZE.saturating_mul(self.scale())
+    }
+
+    // How far the tree-walker's calls may take the stack. Functions full of
+    // nested blocks and expressions cost several times as much per call as a
+    // small one, so the tree-walker also reports a stack overflow once its
+    // calls have used this much, keeping back enough for the nesting within
+    // the deepest call.
+    pub fn call_stack_budget(&self) -> usize {
+        self.stack_size() - NESTING_STACK_SIZE.saturating_mul(self.scale())
+    }
+
+    fn scale(&self) -> usize {
+        let depth = self.max_depth.div_ceil(DEFAULT_MAX_DEPTH);
+        let calls = self.max_call_depth.div_ceil(DEFAULT_MAX_CALL_DEPTH);
+        depth.max(calls).max(1)
+    }
+}
@@ -1,23 +1,38 @@
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
 
+use command::compile::CompileCommand;
+use command::disassemble::DisassembleCommand;
 use command::evaluate::EvaluateCommand;
 use command::parse::ParseCommand;
 use command::run::RunCommand;
 use command::tokenize::TokenizeCommand;
 use command::Command;
 use error::LoxError;
+use limits::Limits;
 
 mod ast;
+mod class;
 mod command;
 mod environment;
 mod error;
+mod function;
+mod gc;
+mod interner;
 mod interpreter;
+mod limits;
+mod native;
+mod optimizer;
 mod parser;
+mod resolver;
 mod scanner;
 mod token;
 mod utils;
+mod value;
+mod vm;
 
 fn main() -> Result<(), LoxError> {
     let args: Vec<String> = env::args().collect();
@@ -25,20 +40,107 @@ fn main() -> Result<(), LoxError> {
         eprintln!("Usage: {} tokenize <filename>", args[0]);
         return Ok(());
     }
+    let options = Options::parse(&args[2..]);
 
-    let command_name = &args[1];
-    let filename = &args[2];
+    let stack_size = options.limits.stack_size();
+    thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || run(&args, options))
+        .unwrap_or_else(|_| {
+            eprintln!(
+                "Can't reserve a {} MiB stack for these limits",
+                stack_size >> 20
+            );
+            process::exit(64);
+        })
+        .join()
+        .unwrap_or_else(|_| process::exit(101))
+}
 
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        eprintln!("Failed to read file {}", filename);
-        String::new()
-    });
+// What follows the command name: `--flags`, `-o <path>`, the limits and the
+// file to work on.
+struct Options {
+    flags: Vec<String>,
+    operands: Vec<String>,
+    output: Option<PathBuf>,
+    limits: Limits,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Self {
+        let mut options = Self {
+            flags: Vec::new(),
+            operands: Vec::new(),
+            output: None,
+            limits: Limits::default(),
+        };
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "-o" => options.output = rest.next().map(PathBuf::from),
+                "--max-depth" => options.limits.max_depth = limit(arg, rest.next()),
+                "--max-call-depth" => options.limits.max_call_depth = limit(arg, rest.next()),
+                _ if arg.starts_with("--") => options.flags.push(arg.clone()),
+                _ => options.operands.push(arg.clone()),
+            }
+        }
+        options
+    }
+}
+
+fn limit(option: &str, value: Option<&String>) -> usize {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| {
+            eprintln!("{} takes a number", option);
+            process::exit(64);
+        })
+}
+
+fn run(args: &[String], options: Options) -> Result<(), LoxError> {
+    let command_name = &args[1];
+    let Options {
+        flags,
+        operands,
+        output,
+        limits,
+    } = options;
+    let has_flag = |name: &str| flags.iter().any(|flag| flag == name);
+    let Some(filename) = operands.first() else {
+        eprintln!("Usage: {} tokenize <filename>", args[0]);
+        return Ok(());
+    };
+    let is_bytecode = Path::new(filename)
+        .extension()
+        .is_some_and(|extension| extension == "loxc");
 
     let command: Box<dyn Command> = match command_name.as_str() {
-        "tokenize" => Box::new(TokenizeCommand::new(file_contents)),
-        "evaluate" => Box::new(EvaluateCommand::new(file_contents)),
-        "parse" => Box::new(ParseCommand::new(file_contents)),
-        "run" => Box::new(RunCommand::new(file_contents)),
+        "tokenize" => Box::new(TokenizeCommand::new(
+            read_source(filename),
+            has_flag("--spans"),
+        )),
+        "evaluate" => Box::new(EvaluateCommand::new(read_source(filename))),
+        "parse" => Box::new(ParseCommand::new(
+            read_source(filename),
+            has_flag("--optimized"),
+        )),
+        "disassemble" => Box::new(DisassembleCommand::new(read_source(filename))),
+        "compile" => {
+            let output = output.unwrap_or_else(|| Path::new(filename).with_extension("loxc"));
+            Box::new(CompileCommand::new(read_source(filename), output))
+        }
+        "run" if is_bytecode => Box::new(
+            RunCommand::from_bytecode(read_bytes(filename), has_flag("--gc-stats"))
+                .with_limits(limits),
+        ),
+        "run" => Box::new(
+            RunCommand::new(
+                read_source(filename),
+                has_flag("--vm"),
+                has_flag("--gc-stats"),
+            )
+            .with_limits(limits),
+        ),
         _ => {
             eprintln!("Unknown command: {}", command_name);
             process::exit(64);
@@ -48,3 +150,17 @@ fn main() -> Result<(), LoxError> {
     let exit_code = command.execute()?;
     process::exit(exit_code.code());
 }
+
+fn read_source(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|_| {
+        eprintln!("Failed to read file {}", filename);
+        String::new()
+    })
+}
+
+fn read_bytes(filename: &str) -> Vec<u8> {
+    fs::read(filename).unwrap_or_else(|_| {
+        eprintln!("Failed to read file {}", filename);
+        Vec::new()
+    })
+}
@@ -0,0 +1,56 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::LoxError, value::Value};
+
+pub type NativeFn = fn(&[Value]) -> Result<Value, LoxError>;
+
+// A function implemented in Rust and exposed to scripts as a global.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, function: NativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function,
+        }
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, LoxError> {
+        (self.function)(arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<native fn>")
+    }
+}
+
+pub fn clock(_arguments: &[Value]) -> Result<Value, LoxError> {
+    Ok(Value::Number(seconds_since_epoch()?))
+}
+
+// Seconds since the Unix epoch, with sub-second precision.
+pub fn seconds_since_epoch() -> Result<f64, LoxError> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| LoxError::new("System clock is set before the Unix epoch", None))?;
+    Ok(elapsed.as_secs_f64())
+}
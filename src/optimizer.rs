@@ -0,0 +1,248 @@
+use std::rc::Rc;
+
+use crate::{
+    ast::{Expr, FunctionDecl, Stmt},
+    interner::Symbol,
+    token::TokenType,
+};
+
+// Folds expressions whose operands are all literals into a single literal.
+// Anything that would fail at runtime, like `"a" - 1` or a division by zero,
+// is left in place so the error is still raised on the operator's line.
+pub fn optimize(statements: Vec<Stmt>) -> Vec<Stmt> {
+    statements.into_iter().map(optimize_stmt).collect()
+}
+
+fn optimize_stmt(stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Print(expr) => Stmt::Print(fold(expr)),
+        Stmt::Expression(expr) => Stmt::Expression(fold(expr)),
+        Stmt::Var {
+            name,
+            initializer,
+            slot,
+        } => Stmt::Var {
+            name,
+            initializer: fold(initializer),
+            slot,
+        },
+        Stmt::Block(statements) => Stmt::Block(optimize(statements)),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Stmt::If {
+            condition: fold(condition),
+            then_branch: Box::new(optimize_stmt(*then_branch)),
+            else_branch: else_branch.map(|branch| Box::new(optimize_stmt(*branch))),
+        },
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => Stmt::While {
+            condition: fold(condition),
+            body: Box::new(optimize_stmt(*body)),
+            increment: increment.map(fold),
+        },
+        Stmt::Break | Stmt::Continue => stmt,
+        Stmt::Function(declaration) => Stmt::Function(optimize_function(declaration)),
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+            slot,
+        } => Stmt::Class {
+            name,
+            superclass,
+            methods: methods.into_iter().map(optimize_function).collect(),
+            slot,
+        },
+        Stmt::Return { keyword, value } => Stmt::Return {
+            keyword,
+            value: value.map(fold),
+        },
+    }
+}
+
+// Declarations are only shared once the interpreter creates functions from
+// them, so straight out of the parser we own the only reference.
+fn optimize_function(declaration: Rc<FunctionDecl>) -> Rc<FunctionDecl> {
+    match Rc::try_unwrap(declaration) {
+        Ok(FunctionDecl {
+            name,
+            params,
+            body,
+            slot,
+        }) => Rc::new(FunctionDecl {
+            name,
+            params,
+            body: optimize(body),
+            slot,
+        }),
+        Err(shared) => shared,
+    }
+}
+
+fn fold(expr: Expr) -> Expr {
+    match expr {
+        Expr::Grouping(inner) => {
+            let inner = fold(*inner);
+            if is_literal(&inner) {
+                inner
+            } else {
+                Expr::Grouping(Box::new(inner))
+            }
+        }
+        Expr::Unary { operator, right } => {
+            let right = fold(*right);
+            match (&operator.token_type, &right) {
+                (TokenType::Minus, Expr::Number(n)) => Expr::Number(-n),
+                (TokenType::Bang, literal) if is_literal(literal) => {
+                    Expr::Boolean(!is_truthy(literal))
+                }
+                _ => Expr::Unary {
+                    operator,
+                    right: Box::new(right),
+                },
+            }
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let left = fold(*left);
+            let right = fold(*right);
+            match fold_binary(&left, &operator.token_type, &right) {
+                Some(folded) => folded,
+                None => Expr::Binary {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                },
+            }
+        }
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => {
+            let left = fold(*left);
+            let right = fold(*right);
+            if !is_literal(&left) {
+                return Expr::Logical {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                };
+            }
+            match (&operator.token_type, is_truthy(&left)) {
+                (TokenType::Or, true) | (TokenType::And, false) => left,
+                _ => right,
+            }
+        }
+        Expr::Interpolation(parts) => fold_interpolation(parts),
+        Expr::Assign { name, value, slot } => Expr::Assign {
+            name,
+            value: Box::new(fold(*value)),
+            slot,
+        },
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => Expr::Call {
+            callee: Box::new(fold(*callee)),
+            paren,
+            arguments: arguments.into_iter().map(fold).collect(),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: Box::new(fold(*object)),
+            name,
+        },
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => Expr::Set {
+            object: Box::new(fold(*object)),
+            name,
+            value: Box::new(fold(*value)),
+        },
+        _ => expr,
+    }
+}
+
+fn fold_binary(left: &Expr, operator: &TokenType, right: &Expr) -> Option<Expr> {
+    if !is_literal(left) || !is_literal(right) {
+        return None;
+    }
+
+    let folded = match (left, operator, right) {
+        (_, TokenType::EqualEqual, _) => Expr::Boolean(left == right),
+        (_, TokenType::BangEqual, _) => Expr::Boolean(left != right),
+        (Expr::Number(l), _, Expr::Number(r)) => match operator {
+            TokenType::Plus => Expr::Number(l + r),
+            TokenType::Minus => Expr::Number(l - r),
+            TokenType::Star => Expr::Number(l * r),
+            TokenType::Slash if *r != 0.0 => Expr::Number(l / r),
+            TokenType::Greater => Expr::Boolean(l > r),
+            TokenType::GreaterEqual => Expr::Boolean(l >= r),
+            TokenType::Less => Expr::Boolean(l < r),
+            TokenType::LessEqual => Expr::Boolean(l <= r),
+            _ => return None,
+        },
+        (Expr::String(l), TokenType::Plus, Expr::String(r)) => {
+            Expr::String(Symbol::intern(&format!("{}{}", l, r)))
+        }
+        _ => return None,
+    };
+    Some(folded)
+}
+
+// Joins runs of literal parts into one string, and the whole interpolation
+// into a plain string when nothing but literals are left.
+fn fold_interpolation(parts: Vec<Expr>) -> Expr {
+    let mut folded: Vec<Expr> = Vec::new();
+    for part in parts.into_iter().map(fold) {
+        let Some(text) = literal_text(&part) else {
+            folded.push(part);
+            continue;
+        };
+        match folded.last_mut() {
+            Some(Expr::String(previous)) => {
+                *previous = Symbol::intern(&format!("{}{}", previous, text));
+            }
+            _ => folded.push(Expr::String(Symbol::intern(&text))),
+        }
+    }
+
+    match folded.as_slice() {
+        [] => Expr::String(Symbol::intern("")),
+        [Expr::String(_)] => folded.pop().unwrap(),
+        _ => Expr::Interpolation(folded),
+    }
+}
+
+// How a literal reads once stringified at runtime.
+fn literal_text(literal: &Expr) -> Option<String> {
+    match literal {
+        Expr::String(s) => Some(s.to_string()),
+        Expr::Number(n) => Some(n.to_string()),
+        Expr::Boolean(b) => Some(b.to_string()),
+        Expr::Nil => Some("nil".to_string()),
+        _ => None,
+    }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::String(_) | Expr::Number(_) | Expr::Boolean(_) | Expr::Nil
+    )
+}
+
+fn is_truthy(literal: &Expr) -> bool {
+    !matches!(literal, Expr::Nil | Expr::Boolean(false))
+}
@@ -1,13 +1,29 @@
+use std::{cell::Cell, rc::Rc};
+
 use crate::{
-    ast::{Expr, Stmt},
+    ast::{Expr, FunctionDecl, Stmt},
     error::LoxError,
+    interner::Symbol,
+    limits::Limits,
     token::{Token, TokenType},
 };
 
+const MAX_ARGUMENTS: usize = 255;
+const MAX_INTERPOLATION_PARTS: usize = 255;
+
+// How many operator and call links may be chained on top of the nesting the
+// limits allow. Chains are parsed in a loop, but every pass after parsing
+// recurses once per link.
+const MAX_CHAIN_LENGTH: usize = 5_000;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     require_semicolon: bool,
+    loop_depth: usize,
+    depth: usize,
+    max_depth: usize,
+    links: usize,
 }
 
 impl Parser {
@@ -16,9 +32,18 @@ impl Parser {
             tokens,
             current: 0,
             require_semicolon,
+            loop_depth: 0,
+            depth: 0,
+            max_depth: Limits::default().max_depth,
+            links: 0,
         }
     }
 
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_depth = limits.max_depth;
+        self
+    }
+
     pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxError> {
         let mut statements = Vec::new();
 
@@ -42,38 +67,232 @@ impl Parser {
             return Ok(Stmt::Block(self.block()?));
         }
 
+        if self.match_token(&[TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.match_token(&[TokenType::While]) {
+            return self.while_statement();
+        }
+
+        if self.match_token(&[TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.match_token(&[TokenType::Fun]) {
+            return Ok(Stmt::Function(self.function()?));
+        }
+
+        if self.match_token(&[TokenType::Class]) {
+            return self.class_declaration();
+        }
+
+        if self.match_token(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_token(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_control_statement();
+        }
+
         self.expression_statement()
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
-        let mut statements = Vec::new();
+    fn class_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.identifier("Expected class name after 'class'")?;
 
+        let superclass = if self.match_token(&[TokenType::Less]) {
+            let superclass = self.identifier("Expected superclass name after '<'")?;
+            Some(Expr::variable(superclass))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace)?;
+
+        let mut methods = Vec::new();
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.statement()?);
+            methods.push(self.function()?);
         }
 
         self.consume(TokenType::RightBrace)?;
 
-        Ok(statements)
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            slot: Cell::new(None),
+        })
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
-        let value = self.expression()?;
+    fn function(&mut self) -> Result<Rc<FunctionDecl>, LoxError> {
+        let name = self.identifier("Expected function name")?;
+
+        self.consume(TokenType::LeftParen)?;
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    return Err(LoxError::at(
+                        self.peek(),
+                        &format!("Can't have more than {} parameters", MAX_ARGUMENTS),
+                    ));
+                }
+                params.push(self.identifier("Expected parameter name")?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen)?;
+
+        self.consume(TokenType::LeftBrace)?;
+        // A function body starts outside of any loop, even when declared in one.
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block();
+        self.loop_depth = enclosing_loop_depth;
+        let body = body?;
+
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body,
+            slot: Cell::new(None),
+        }))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
         self.consume(TokenType::Semicolon)?;
-        Ok(Stmt::Print(value))
+
+        Ok(Stmt::Return { keyword, value })
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
-        let name = if let TokenType::Identifier = self.peek().token_type {
-            self.peek().lexeme.clone()
+    fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen)?;
+
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.nested(Self::statement)?))
         } else {
-            return Err(LoxError::new(
-                "Expected variable name after 'var'",
-                Some(self.peek().line),
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen)?;
+        let body = Box::new(self.loop_body()?);
+
+        Ok(Stmt::While {
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    fn loop_body(&mut self) -> Result<Stmt, LoxError> {
+        self.loop_depth += 1;
+        let body = self.nested(Self::statement);
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn loop_control_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            return Err(LoxError::at(
+                &keyword,
+                &format!("Can't use '{}' outside of a loop", keyword.lexeme),
             ));
+        }
+        self.consume(TokenType::Semicolon)?;
+
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break)
+        } else {
+            Ok(Stmt::Continue)
+        }
+    }
+
+    // A `for` loop is desugared into an optional initializer followed by a
+    // `while` loop that runs the increment after each pass through the body.
+    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen)?;
+
+        let initializer = if self.match_token(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            let expr = self.expression()?;
+            self.consume(TokenType::Semicolon)?;
+            Some(Stmt::Expression(expr))
         };
 
-        self.advance();
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expr::Boolean(true)
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon)?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen)?;
+
+        let mut body = Stmt::While {
+            condition,
+            body: Box::new(self.loop_body()?),
+            increment,
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        self.nested(|parser| {
+            let mut statements = Vec::new();
+
+            while !parser.check(&TokenType::RightBrace) && !parser.is_at_end() {
+                statements.push(parser.statement()?);
+            }
+
+            parser.consume(TokenType::RightBrace)?;
+
+            Ok(statements)
+        })
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.identifier("Expected variable name after 'var'")?;
 
         let initializer = if self.match_token(&[TokenType::Equal]) {
             Some(self.expression()?)
@@ -83,7 +302,11 @@ impl Parser {
 
         self.consume(TokenType::Semicolon)?;
 
-        Ok(Stmt::Var(name, initializer.unwrap_or(Expr::Nil)))
+        Ok(Stmt::Var {
+            name,
+            initializer: initializer.unwrap_or(Expr::Nil),
+            slot: Cell::new(None),
+        })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
@@ -95,35 +318,78 @@ impl Parser {
     }
 
     fn expression(&mut self) -> Result<Expr, LoxError> {
-        self.assignment()
+        self.nested(Self::assignment)
     }
 
     fn assignment(&mut self) -> Result<Expr, LoxError> {
-        let expr = self.equality()?;
+        let expr = self.or()?;
 
         if self.match_token(&[TokenType::Equal]) {
             let equals = self.previous().clone();
-            let value = self.assignment()?;
+            let value = self.nested(Self::assignment)?;
 
-            if let Expr::Variable(ref name) = expr {
-                return Ok(Expr::Assign {
-                    name: name.clone(),
+            return match expr {
+                Expr::Variable { name, slot } => Ok(Expr::Assign {
+                    name,
                     value: Box::new(value),
-                });
-            } else {
-                return Err(LoxError::new(
-                    "Invalid assignment target",
-                    Some(equals.line),
-                ));
-            }
+                    slot,
+                }),
+                Expr::Get { object, name } => Ok(Expr::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                }),
+                _ => Err(LoxError::at(&equals, "Invalid assignment target")),
+            };
         }
 
         Ok(expr)
     }
+
+    fn or(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
+        let mut expr = self.and()?;
+
+        while self.match_token(&[TokenType::Or]) {
+            self.link()?;
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        self.links = links;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
+        let mut expr = self.equality()?;
+
+        while self.match_token(&[TokenType::And]) {
+            self.link()?;
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        self.links = links;
+        Ok(expr)
+    }
+
     fn equality(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
         let mut expr = self.relational()?;
 
         while self.match_token(&[TokenType::EqualEqual, TokenType::BangEqual]) {
+            self.link()?;
             let operator = self.previous().clone();
             let right = self.relational()?;
             expr = Expr::Binary {
@@ -133,10 +399,12 @@ impl Parser {
             };
         }
 
+        self.links = links;
         Ok(expr)
     }
 
     fn relational(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
         let mut expr = self.addition()?;
 
         while self.match_token(&[
@@ -145,6 +413,7 @@ impl Parser {
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
+            self.link()?;
             let operator = self.previous().clone();
             let right = self.addition()?;
             expr = Expr::Binary {
@@ -154,13 +423,16 @@ impl Parser {
             };
         }
 
+        self.links = links;
         Ok(expr)
     }
 
     fn addition(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
         let mut expr = self.multiplication()?;
 
         while self.match_token(&[TokenType::Plus, TokenType::Minus]) {
+            self.link()?;
             let operator = self.previous().clone();
             let right = self.multiplication()?;
             expr = Expr::Binary {
@@ -170,13 +442,16 @@ impl Parser {
             };
         }
 
+        self.links = links;
         Ok(expr)
     }
 
     fn multiplication(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
         let mut expr = self.unary()?;
 
         while self.match_token(&[TokenType::Star, TokenType::Slash]) {
+            self.link()?;
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary {
@@ -186,47 +461,168 @@ impl Parser {
             };
         }
 
+        self.links = links;
         Ok(expr)
     }
 
     fn unary(&mut self) -> Result<Expr, LoxError> {
         if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().clone();
-            let right = Box::new(self.unary()?);
+            let right = Box::new(self.nested(Self::unary)?);
             return Ok(Expr::Unary { operator, right });
         }
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, LoxError> {
+        let links = self.links;
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.check(&TokenType::LeftParen) || self.check(&TokenType::Dot) {
+                self.link()?;
+            }
+            if self.match_token(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenType::Dot]) {
+                if !self.check(&TokenType::Identifier) {
+                    return Err(LoxError::at(
+                        self.peek(),
+                        "Expected property name after '.'",
+                    ));
+                }
+                let name = self.advance().clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        self.links = links;
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    return Err(LoxError::at(
+                        self.peek(),
+                        &format!("Can't have more than {} arguments", MAX_ARGUMENTS),
+                    ));
+                }
+                arguments.push(self.expression()?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen)?;
+        let paren = self.previous().clone();
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
     }
 
     fn primary(&mut self) -> Result<Expr, LoxError> {
         if self.is_at_end() {
-            return Err(LoxError::new(
-                "Unexpected end of input",
-                Some(self.peek().line),
-            ));
+            return Err(LoxError::at(self.peek(), "Unexpected end of input"));
         }
 
         let token = self.advance();
         let lexeme = token.lexeme.clone();
         match token.token_type {
-            TokenType::String => Ok(Expr::String(lexeme)),
+            TokenType::String => Ok(Expr::String(Symbol::intern(
+                token.literal.as_deref().unwrap_or_default(),
+            ))),
             TokenType::Number => Ok(Expr::Number(lexeme.parse::<f64>().unwrap_or(0.0))),
+            TokenType::Interpolation => self.interpolation(),
             TokenType::True => Ok(Expr::Boolean(true)),
             TokenType::False => Ok(Expr::Boolean(false)),
             TokenType::Nil => Ok(Expr::Nil),
-            TokenType::Identifier => Ok(Expr::Variable(lexeme)),
+            TokenType::Identifier => Ok(Expr::variable(token.clone())),
+            TokenType::This => Ok(Expr::This {
+                keyword: token.clone(),
+                slot: Cell::new(None),
+            }),
+            TokenType::Super => {
+                let keyword = token.clone();
+                self.consume(TokenType::Dot)?;
+                if !self.check(&TokenType::Identifier) {
+                    return Err(LoxError::at(
+                        self.peek(),
+                        "Expected superclass method name after 'super.'",
+                    ));
+                }
+                let method = self.advance().clone();
+                Ok(Expr::Super {
+                    keyword,
+                    method,
+                    slot: Cell::new(None),
+                })
+            }
             TokenType::LeftParen => {
                 let expr = self.expression()?;
                 self.consume(TokenType::RightParen)?;
                 Ok(Expr::Grouping(Box::new(expr)))
             }
-            _ => Err(LoxError::new(
+            _ => Err(LoxError::at(
+                token,
                 &format!("Unexpected token: '{}'", token.lexeme),
-                Some(token.line),
             )),
         }
     }
 
+    // Parses the rest of an interpolated string once its first part has been
+    // consumed. Empty text between expressions is left out.
+    fn interpolation(&mut self) -> Result<Expr, LoxError> {
+        let mut parts = Vec::new();
+        loop {
+            let text = self.previous().literal.as_deref().unwrap_or_default();
+            if !text.is_empty() {
+                parts.push(Expr::String(Symbol::intern(text)));
+            }
+            if self.previous().token_type == TokenType::InterpolationEnd {
+                break;
+            }
+            parts.push(self.expression()?);
+
+            if !self.match_token(&[TokenType::InterpolationMiddle, TokenType::InterpolationEnd]) {
+                return Err(LoxError::at(
+                    self.peek(),
+                    "Expected '}' after interpolated expression",
+                ));
+            }
+        }
+
+        if parts.len() > MAX_INTERPOLATION_PARTS {
+            return Err(LoxError::at(
+                self.previous(),
+                &format!(
+                    "Can't have more than {} parts in an interpolated string",
+                    MAX_INTERPOLATION_PARTS
+                ),
+            ));
+        }
+        Ok(Expr::Interpolation(parts))
+    }
+
+    fn identifier(&mut self, message: &str) -> Result<Token, LoxError> {
+        if let TokenType::Identifier = self.peek().token_type {
+            Ok(self.advance().clone())
+        } else {
+            Err(LoxError::at(self.peek(), message))
+        }
+    }
+
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -246,24 +642,55 @@ impl Parser {
     fn consume(&mut self, expected: TokenType) -> Result<(), LoxError> {
         if self.is_at_end() {
             let eof_token = self.peek();
-            return Err(LoxError::new(
+            return Err(LoxError::at(
+                eof_token,
                 &format!("Expected {:?}, but got Eof", expected),
-                Some(eof_token.line),
             ));
         } else if self.peek().token_type != expected {
             let current_token = self.peek();
-            return Err(LoxError::new(
+            return Err(LoxError::at(
+                current_token,
                 &format!(
                     "Expected {:?}, but got {:?}",
                     expected, current_token.token_type
                 ),
-                Some(current_token.line),
             ));
         }
         self.advance();
         Ok(())
     }
 
+    // Parses one level further in, failing once nesting passes `max_depth`.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, LoxError>,
+    ) -> Result<T, LoxError> {
+        if self.depth >= self.max_depth {
+            return Err(LoxError::at(
+                self.peek(),
+                &format!("Maximum nesting depth of {} exceeded", self.max_depth),
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Left-associative operators and call chains deepen the tree in a loop
+    // rather than by recursing, so they call this for each link and restore
+    // the count themselves once the chain ends.
+    fn link(&mut self) -> Result<(), LoxError> {
+        if self.links >= MAX_CHAIN_LENGTH {
+            return Err(LoxError::at(
+                self.peek(),
+                &format!("Maximum expression length of {} exceeded", MAX_CHAIN_LENGTH),
+            ));
+        }
+        self.links += 1;
+        Ok(())
+    }
+
     fn match_token(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
             if self.check(token_type) {
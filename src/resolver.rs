@@ -0,0 +1,339 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{Expr, FunctionDecl, Slot, Stmt},
+    error::LoxError,
+    interner::Symbol,
+    token::Token,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// A variable declared in a local scope: its index among the scope's
+// variables, and whether its initializer has finished resolving.
+struct Local {
+    index: usize,
+    defined: bool,
+}
+
+// Walks the parsed program once before it runs, giving every local variable
+// a slot in its scope and recording for every use how many scopes separate it
+// from its declaration, and rejecting programs that are statically invalid.
+pub struct Resolver {
+    scopes: Vec<HashMap<Symbol, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
+        for stmt in statements {
+            self.resolve_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) -> Result<(), LoxError> {
+        match stmt {
+            Stmt::Print(expr) | Stmt::Expression(expr) => self.resolve_expr(expr)?,
+            Stmt::Var {
+                name,
+                initializer,
+                slot,
+            } => {
+                slot.set(self.declare(name)?);
+                self.resolve_expr(initializer)?;
+                self.define(name);
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.resolve(statements)?;
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition)?;
+                self.resolve_stmt(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.resolve_expr(condition)?;
+                self.resolve_stmt(body)?;
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment)?;
+                }
+            }
+            Stmt::Break | Stmt::Continue => {}
+            Stmt::Function(declaration) => {
+                declaration.slot.set(self.declare(&declaration.name)?);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function)?;
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                slot,
+            } => {
+                slot.set(self.declare(name)?);
+                self.define(name);
+                self.resolve_class(name, superclass.as_ref(), methods)?;
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    return Err(LoxError::at(keyword, "Can't return from top-level code"));
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        return Err(LoxError::at(
+                            keyword,
+                            "Can't return a value from an initializer",
+                        ));
+                    }
+                    self.resolve_expr(value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Result<(), LoxError> {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable {
+                name: superclass_name,
+                ..
+            } = superclass
+            {
+                if superclass_name.lexeme == name.lexeme {
+                    return Err(LoxError::at(
+                        superclass_name,
+                        "A class can't inherit from itself",
+                    ));
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass)?;
+
+            self.begin_scope();
+            self.insert(Symbol::intern("super"));
+        }
+
+        self.begin_scope();
+        self.insert(Symbol::intern("this"));
+
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type)?;
+        }
+
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+        Ok(())
+    }
+
+    fn resolve_function(
+        &mut self,
+        declaration: &FunctionDecl,
+        function_type: FunctionType,
+    ) -> Result<(), LoxError> {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param)?;
+            self.define(param);
+        }
+        self.resolve(&declaration.body)?;
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        Ok(())
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) -> Result<(), LoxError> {
+        match expr {
+            Expr::String(_) | Expr::Number(_) | Expr::Boolean(_) | Expr::Nil => {}
+            Expr::Unary { right, .. } => self.resolve_expr(right)?,
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left)?;
+                self.resolve_expr(right)?;
+            }
+            Expr::Grouping(inner) => self.resolve_expr(inner)?,
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.resolve_expr(part)?;
+                }
+            }
+            Expr::Variable { name, slot } => {
+                let local = self.scopes.last().and_then(|scope| scope.get(&name.lexeme));
+                if let Some(Local { defined: false, .. }) = local {
+                    return Err(LoxError::at(
+                        name,
+                        "Can't read local variable in its own initializer",
+                    ));
+                }
+                self.resolve_local(&name.lexeme, slot);
+            }
+            Expr::Assign { name, value, slot } => {
+                self.resolve_expr(value)?;
+                self.resolve_local(&name.lexeme, slot);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee)?;
+                for argument in arguments {
+                    self.resolve_expr(argument)?;
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object)?,
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value)?;
+                self.resolve_expr(object)?;
+            }
+            Expr::This { keyword, slot } => {
+                if self.current_class == ClassType::None {
+                    return Err(LoxError::at(keyword, "Can't use 'this' outside of a class"));
+                }
+                self.resolve_local(&Symbol::intern("this"), slot);
+            }
+            Expr::Super { keyword, slot, .. } => {
+                match self.current_class {
+                    ClassType::None => {
+                        return Err(LoxError::at(
+                            keyword,
+                            "Can't use 'super' outside of a class",
+                        ))
+                    }
+                    ClassType::Class => {
+                        return Err(LoxError::at(
+                            keyword,
+                            "Can't use 'super' in a class with no superclass",
+                        ))
+                    }
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(&Symbol::intern("super"), slot);
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_local(&self, name: &Symbol, slot: &Cell<Option<Slot>>) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                scope.get(name).map(|local| Slot {
+                    depth,
+                    index: local.index,
+                })
+            });
+        slot.set(found);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Returns the slot given to the new variable, or `None` for a global.
+    fn declare(&mut self, name: &Token) -> Result<Option<usize>, LoxError> {
+        let Some(scope) = self.scopes.last_mut() else {
+            return Ok(None);
+        };
+        if scope.contains_key(&name.lexeme) {
+            return Err(LoxError::at(
+                name,
+                "Already a variable with this name in this scope",
+            ));
+        }
+
+        let index = scope.len();
+        scope.insert(
+            name.lexeme.clone(),
+            Local {
+                index,
+                defined: false,
+            },
+        );
+        Ok(Some(index))
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
+        }
+    }
+
+    // Declares and defines a variable the interpreter binds implicitly, like
+    // `this` and `super`, as the first slot of a fresh scope.
+    fn insert(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let index = scope.len();
+            scope.insert(
+                name,
+                Local {
+                    index,
+                    defined: true,
+                },
+            );
+        }
+    }
+}
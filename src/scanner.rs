@@ -1,6 +1,6 @@
 use crate::{
     error::LoxError,
-    token::{Token, TokenType},
+    token::{Span, Token, TokenType},
 };
 
 pub struct Scanner {
@@ -9,6 +9,17 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    // Character columns of the next character and of the token being scanned.
+    column: usize,
+    start_column: usize,
+    // The line the token being scanned starts on. Strings and block comments
+    // can run past it.
+    start_line: usize,
+    // One entry per string interpolation being scanned, counting the braces
+    // opened inside it, so the `}` that ends it can be told apart.
+    interpolations: Vec<usize>,
+    // Report errors with their column as well as their line.
+    spans: bool,
     has_error: bool,
 }
 
@@ -20,21 +31,45 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_column: 1,
+            start_line: 1,
+            interpolations: Vec::new(),
+            spans: false,
             has_error: false,
         }
     }
 
+    pub fn with_spans(mut self, spans: bool) -> Self {
+        self.spans = spans;
+        self
+    }
+
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_column = self.column;
+            self.start_line = self.line;
             if let Err(err) = self.scan_token() {
                 self.report_error(err);
                 self.has_error = true;
             }
         }
 
+        if !self.interpolations.is_empty() {
+            self.report_error(LoxError::new(
+                "Unterminated string interpolation.",
+                Some(self.line),
+            ));
+            self.has_error = true;
+        }
+
+        self.start = self.current;
+        self.start_column = self.column;
+        self.start_line = self.line;
+        let span = self.span();
         self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), None, self.line));
+            .push(Token::new(TokenType::Eof, "", None, self.line, span));
 
         &self.tokens
     }
@@ -42,20 +77,25 @@ impl Scanner {
     fn scan_token(&mut self) -> Result<(), LoxError> {
         let c = self.advance();
         match c {
-            'p' if self.look_ahead(5) == "print" => {
-                self.add_token(TokenType::Print)?;
-                self.current += 4;
-                Ok(())
-            }
-            'v' if self.look_ahead(2) == "var" => {
-                self.add_token(TokenType::Var)?;
-                self.current += 2;
-                Ok(())
-            }
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(true)
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
             '+' => self.add_token(TokenType::Plus),
@@ -67,11 +107,13 @@ impl Scanner {
                         self.advance();
                     }
                     Ok(())
+                } else if self.match_next('*') {
+                    self.block_comment()
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            '"' => self.string(),
+            '"' => self.string(false),
             '=' => {
                 if self.match_next('=') {
                     self.add_token(TokenType::EqualEqual)
@@ -108,44 +150,200 @@ impl Scanner {
             }
             c if c.is_alphabetic() || c == '_' => self.identifier(),
             c if c.is_ascii_digit() => self.number(),
-            _ => Err(LoxError::new(
-                &format!("Unexpected character: {}", c),
-                Some(self.line),
-            )),
+            _ => Err(
+                LoxError::new(&format!("Unexpected character: {}", c), Some(self.line))
+                    .with_span(self.span()),
+            ),
+        }
+    }
+
+    // Skips a `/* ... */` comment, which may contain further block comments.
+    // An unterminated one is reported at the line it opened on.
+    fn block_comment(&mut self) -> Result<(), LoxError> {
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(
+                    LoxError::new("Unterminated block comment.", Some(self.start_line))
+                        .with_span(self.span()),
+                );
+            }
+
+            match self.advance() {
+                '/' if self.match_next('*') => depth += 1,
+                '*' if self.match_next('/') => depth -= 1,
+                '\n' => self.line += 1,
+                _ => {}
+            }
         }
+        Ok(())
     }
 
     fn add_token(&mut self, token_type: TokenType) -> Result<(), LoxError> {
-        let lexeme = self.source[self.start..self.current].to_string();
+        let lexeme = &self.source[self.start..self.current];
+        let span = self.span();
         self.tokens
-            .push(Token::new(token_type, lexeme, None, self.line));
+            .push(Token::new(token_type, lexeme, None, self.line, span));
         Ok(())
     }
 
-    fn string(&mut self) -> Result<(), LoxError> {
+    // Scans string text up to the closing quote, or up to a `${` that starts
+    // an embedded expression. Called after the opening quote, and again after
+    // the `}` that closes each embedded expression. The parts after a `}` get
+    // token types of their own, and keep the `}` in their lexeme.
+    fn string(&mut self, continued: bool) -> Result<(), LoxError> {
+        let text_start = if continued {
+            self.start
+        } else {
+            self.start + 1
+        };
+        let mut value = String::new();
+        // Keep scanning to the closing quote after a bad escape, so the rest
+        // of the string isn't mistaken for code.
+        let mut error = None;
+
         while !self.is_at_end() && self.peek() != '"' {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '$' && self.peek_next() == '{' {
+                self.advance();
+                self.advance();
+                self.interpolations.push(0);
+                if let Some(err) = error {
+                    return Err(err);
+                }
+
+                let token_type = if continued {
+                    TokenType::InterpolationMiddle
+                } else {
+                    TokenType::Interpolation
+                };
+                let lexeme = &self.source[text_start..self.current - 2];
+                self.tokens.push(Token::new(
+                    token_type,
+                    lexeme,
+                    Some(value),
+                    self.start_line,
+                    self.span(),
+                ));
+                return Ok(());
+            }
+
+            match self.advance() {
+                '\\' if !self.is_at_end() => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                },
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    value.push(c);
+                }
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            return Err(LoxError::new("Unterminated string.", Some(self.line)));
+            return Err(
+                LoxError::new("Unterminated string.", Some(self.start_line)).with_span(self.span())
+            );
         }
 
         self.advance(); // Skip the closing quote
 
-        let value = self.source[self.start + 1..self.current - 1].to_string();
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        let token_type = if continued {
+            TokenType::InterpolationEnd
+        } else {
+            TokenType::String
+        };
+        let lexeme = &self.source[text_start..self.current - 1];
         self.tokens.push(Token::new(
-            TokenType::String,
-            value.clone(),
+            token_type,
+            lexeme,
             Some(value),
-            self.line,
+            self.start_line,
+            self.span(),
         ));
         Ok(())
     }
 
+    // Decodes the escape sequence following a backslash.
+    fn escape(&mut self) -> Result<char, LoxError> {
+        let escape_start = (self.current - 1, self.column - 1);
+        let c = self.advance();
+        let decoded = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '0' => '\0',
+            '$' => '$',
+            'u' => return self.unicode_escape(escape_start),
+            _ => {
+                let error = self.escape_error(escape_start);
+                if c == '\n' {
+                    self.line += 1;
+                }
+                return Err(error);
+            }
+        };
+        Ok(decoded)
+    }
+
+    // Decodes the `{XXXX}` part of a `\u{XXXX}` escape: one to six hex digits
+    // naming a Unicode scalar value.
+    fn unicode_escape(&mut self, escape_start: (usize, usize)) -> Result<char, LoxError> {
+        let digits_start = self.current + 1;
+        let well_formed = self.match_next('{') && {
+            while self.peek().is_ascii_hexdigit() {
+                self.advance();
+            }
+            let digits = self.current - digits_start;
+            (1..=6).contains(&digits) && self.match_next('}')
+        };
+        if !well_formed {
+            return Err(self.escape_error(escape_start));
+        }
+
+        let digits = &self.source[digits_start..self.current - 1];
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                LoxError::new(
+                    &format!("Invalid code point in escape: \\u{{{}}}", digits),
+                    Some(self.line),
+                )
+                .with_span(self.escape_span(escape_start))
+            })
+    }
+
+    fn escape_error(&self, escape_start: (usize, usize)) -> LoxError {
+        let span = self.escape_span(escape_start);
+        let sequence = self.source[span.start..span.end()].trim_end();
+        LoxError::new(
+            &format!("Invalid escape sequence: {}", sequence),
+            Some(self.line),
+        )
+        .with_span(span)
+    }
+
+    // The span of an escape sequence inside a string, from its backslash up
+    // to the current character.
+    fn escape_span(&self, (start, column): (usize, usize)) -> Span {
+        Span {
+            start,
+            length: self.current - start,
+            column,
+        }
+    }
+
     fn number(&mut self) -> Result<(), LoxError> {
         while self.peek().is_ascii_digit() {
             self.advance();
@@ -159,13 +357,14 @@ impl Scanner {
             }
         }
 
-        let value = self.source[self.start..self.current].to_string();
+        let value = &self.source[self.start..self.current];
 
         self.tokens.push(Token::new(
             TokenType::Number,
-            value.clone(),
-            Some(value),
+            value,
+            Some(value.to_string()),
             self.line,
+            self.span(),
         ));
         Ok(())
     }
@@ -175,10 +374,12 @@ impl Scanner {
             self.advance();
         }
 
-        let lexeme = self.source[self.start..self.current].to_string();
-        let token_type = match lexeme.as_str() {
+        let lexeme = &self.source[self.start..self.current];
+        let token_type = match lexeme {
             "and" => TokenType::And,
+            "break" => TokenType::Break,
             "class" => TokenType::Class,
+            "continue" => TokenType::Continue,
             "else" => TokenType::Else,
             "false" => TokenType::False,
             "for" => TokenType::For,
@@ -195,27 +396,37 @@ impl Scanner {
             "while" => TokenType::While,
             _ => TokenType::Identifier,
         };
+        let span = self.span();
         self.tokens
-            .push(Token::new(token_type, lexeme, None, self.line));
+            .push(Token::new(token_type, lexeme, None, self.line, span));
         Ok(())
     }
 
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            length: self.current - self.start,
+            column: self.start_column,
+        }
+    }
+
     fn advance(&mut self) -> char {
         let c = self.source[self.current..].chars().next().unwrap();
         self.current += c.len_utf8();
+        if c == '\n' {
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         c
     }
 
     fn match_next(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
 
-        if self.source.chars().nth(self.current) != Some(expected) {
-            return false;
-        }
-
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -235,17 +446,11 @@ impl Scanner {
         self.peek() == '\0' || self.current >= self.source.len()
     }
 
-    fn look_ahead(&self, length: usize) -> String {
-        let start = self.current;
-        let end = (start + length).min(self.source.len());
-        self.source[start..end].to_string()
-    }
-
     fn report_error(&self, error: LoxError) {
-        if let Some(line) = error.line {
-            eprintln!("[line {}] Error: {}", line, error.message);
+        if self.spans {
+            eprintln!("{:#}", error);
         } else {
-            eprintln!("Error: {}", error.message);
+            eprintln!("{}", error);
         }
     }
 
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::interner::Symbol;
+
 #[derive(Debug, Clone, PartialEq)]
 #[allow(unused)]
 pub enum TokenType {
@@ -28,12 +30,20 @@ pub enum TokenType {
 
     // Literals
     String,
+    // The text of a string up to a `${`. The embedded expression's tokens
+    // follow, then the text from its `}` up to either another `${` or the
+    // closing quote.
+    Interpolation,
+    InterpolationMiddle,
+    InterpolationEnd,
     Number,
     Identifier,
 
     // Keywords
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     For,
@@ -51,27 +61,45 @@ pub enum TokenType {
     Eof,
 }
 
+// The stretch of source a token was scanned from: `start` and `length` are
+// in bytes, `column` is 1-based and counts characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub length: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[allow(unused)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub literal: Option<String>,
     pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: &str,
         literal: Option<String>,
         line: usize,
+        span: Span,
     ) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: Symbol::intern(lexeme),
             literal,
             line,
+            span,
         }
     }
 }
@@ -99,10 +127,15 @@ impl fmt::Display for TokenType {
             TokenType::Greater => write!(fmt, "GREATER"),
             TokenType::GreaterEqual => write!(fmt, "GREATER_EQUAL"),
             TokenType::String => write!(fmt, "STRING"),
+            TokenType::Interpolation => write!(fmt, "INTERPOLATION"),
+            TokenType::InterpolationMiddle => write!(fmt, "INTERPOLATION_MIDDLE"),
+            TokenType::InterpolationEnd => write!(fmt, "INTERPOLATION_END"),
             TokenType::Number => write!(fmt, "NUMBER"),
             TokenType::Identifier => write!(fmt, "IDENTIFIER"),
             TokenType::And => write!(fmt, "AND"),
+            TokenType::Break => write!(fmt, "BREAK"),
             TokenType::Class => write!(fmt, "CLASS"),
+            TokenType::Continue => write!(fmt, "CONTINUE"),
             TokenType::Else => write!(fmt, "ELSE"),
             TokenType::False => write!(fmt, "FALSE"),
             TokenType::For => write!(fmt, "FOR"),
@@ -0,0 +1,64 @@
+use std::{fmt, rc::Rc};
+
+use crate::{
+    class::{LoxClass, LoxInstance},
+    function::LoxFunction,
+    interner::Symbol,
+    native::NativeFunction,
+};
+
+// A runtime value produced by evaluating an expression.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Symbol),
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<LoxInstance>),
+}
+
+impl Value {
+    // `nil` and `false` are falsey; everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            _ => true,
+        }
+    }
+}
+
+// Primitives compare by value, heap objects by identity.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(l), Value::Boolean(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(fmt, "nil"),
+            Value::Boolean(b) => write!(fmt, "{}", b),
+            Value::Number(n) => write!(fmt, "{}", n),
+            Value::String(s) => write!(fmt, "{}", s),
+            Value::Function(function) => write!(fmt, "{}", function),
+            Value::NativeFunction(function) => write!(fmt, "{}", function),
+            Value::Class(class) => write!(fmt, "{}", class),
+            Value::Instance(instance) => write!(fmt, "{}", instance),
+        }
+    }
+}
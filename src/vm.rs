@@ -0,0 +1,716 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{error::LoxError, limits::Limits, native};
+use chunk::{Constant, FunctionProto, OpCode};
+use heap::{GcStats, Heap, ObjRef};
+use object::{BoundMethod, Class, Closure, Function, Instance, Native, NativeFn, Object, Upvalue};
+use value::Value;
+
+pub mod bytecode;
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod heap;
+pub mod object;
+pub mod value;
+
+// A function invocation in progress. `base` is the stack index of slot zero.
+struct CallFrame {
+    closure: ObjRef,
+    proto: Rc<FunctionProto>,
+    constants: Rc<[Value]>,
+    ip: usize,
+    base: usize,
+}
+
+// Executes compiled bytecode. Runtime errors carry the same messages and
+// lines as the tree-walking interpreter.
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    // Upvalues still pointing into the stack, so closures created in the same
+    // scope share them.
+    open_upvalues: Vec<ObjRef>,
+    // Calls that may be in progress, besides the script's own frame.
+    max_call_depth: usize,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Self {
+            heap: Heap::new(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            max_call_depth: Limits::default().max_call_depth,
+        };
+        vm.define_native("clock", 0, clock);
+        vm
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_call_depth = limits.max_call_depth;
+        self
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = self.heap.alloc(Object::Native(Native { arity, function }));
+        self.globals.insert(name.into(), Value::Obj(native));
+    }
+
+    pub fn interpret(&mut self, script: Rc<FunctionProto>) -> Result<(), LoxError> {
+        let function = self.load_function(script);
+        let closure = self.heap.alloc(Object::Closure(Closure {
+            function,
+            upvalues: Vec::new(),
+        }));
+        self.push(Value::Obj(closure));
+        self.call_closure(closure, 0)?;
+
+        let result = self.run();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    // Allocates an object produced while running, collecting garbage first
+    // when the heap has grown enough. Anything the new object refers to must
+    // already be reachable from a root.
+    fn alloc(&mut self, object: Object) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn collect_garbage(&mut self) {
+        for &value in &self.stack {
+            self.heap.mark_value(value);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        for &value in self.globals.values() {
+            self.heap.mark_value(value);
+        }
+        for &upvalue in &self.open_upvalues {
+            self.heap.mark_object(upvalue);
+        }
+        self.heap.collect();
+    }
+
+    // Turns a compiled prototype into a heap function, allocating its string
+    // constants and nested functions along the way. Nothing here is rooted
+    // yet, so it allocates without collecting.
+    fn load_function(&mut self, proto: Rc<FunctionProto>) -> ObjRef {
+        let constants: Vec<Value> = proto
+            .chunk
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Constant::Number(n) => Value::Number(*n),
+                Constant::String(s) => {
+                    Value::Obj(self.heap.alloc(Object::String(s.as_str().into())))
+                }
+                Constant::Function(function) => Value::Obj(self.load_function(Rc::clone(function))),
+            })
+            .collect();
+        self.heap.alloc(Object::Function(Function {
+            proto,
+            constants: constants.into(),
+        }))
+    }
+
+    fn run(&mut self) -> Result<(), LoxError> {
+        loop {
+            let byte = self.read_byte();
+            let Some(op) = OpCode::from_byte(byte) else {
+                return Err(self.runtime_error(&format!("Unknown opcode {}", byte)));
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Boolean(true)),
+                OpCode::False => self.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let value = self.stack[self.frame().base + slot];
+                    self.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.stack[base + slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(&value) => self.push(value),
+                        None => return Err(self.runtime_error("Undefined variable")),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0);
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.runtime_error("Undefined variable")),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let upvalue = self.frame_upvalue();
+                    let value = match self.heap.get(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot],
+                        Object::Upvalue(Upvalue::Closed(value)) => *value,
+                        _ => unreachable!("closures only capture upvalues"),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let upvalue = self.frame_upvalue();
+                    let value = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Object::Upvalue(Upvalue::Closed(closed)) => *closed = value,
+                        _ => unreachable!("closures only capture upvalues"),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let Some(instance) = self.as_instance(self.peek(0)) else {
+                        return Err(self.runtime_error("Only instances have properties"));
+                    };
+                    if let Some(&value) = instance.fields.get(&name) {
+                        self.pop();
+                        self.push(value);
+                    } else {
+                        let class = instance.class;
+                        self.bind_method(class, &name)?;
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let Value::Obj(instance) = self.peek(1) else {
+                        return Err(self.runtime_error("Only instances have fields"));
+                    };
+                    let value = self.peek(0);
+                    let stored = self.heap.update(instance, |object| match object {
+                        Object::Instance(instance) => {
+                            instance.fields.insert(name, value);
+                            true
+                        }
+                        _ => false,
+                    });
+                    if !stored {
+                        return Err(self.runtime_error("Only instances have fields"));
+                    }
+                    self.pop();
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = self.pop();
+                    let Some(superclass) = self.class_ref(superclass) else {
+                        return Err(self.runtime_error("Superclass must be a class"));
+                    };
+                    self.bind_method(superclass, &name)?;
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Boolean(self.heap.values_equal(a, b)));
+                }
+                OpCode::Greater => self.comparison(|a, b| a > b)?,
+                OpCode::GreaterEqual => self.comparison(|a, b| a >= b)?,
+                OpCode::Less => self.comparison(|a, b| a < b)?,
+                OpCode::LessEqual => self.comparison(|a, b| a <= b)?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.arithmetic(|a, b| a - b)?,
+                OpCode::Multiply => self.arithmetic(|a, b| a * b)?,
+                OpCode::Divide => {
+                    if let (Value::Number(_), Value::Number(b)) = (self.peek(1), self.peek(0)) {
+                        if b == 0.0 {
+                            return Err(self.runtime_error("Division by zero"));
+                        }
+                    }
+                    self.arithmetic(|a, b| a / b)?
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(n) = self.peek(0) else {
+                        return Err(self.runtime_error("Operand must be a number"));
+                    };
+                    self.pop();
+                    self.push(Value::Number(-n));
+                }
+                OpCode::Interpolate => {
+                    let part_count = self.read_byte() as usize;
+                    let parts = self.stack.len() - part_count;
+                    let string: String = self.stack[parts..]
+                        .iter()
+                        .map(|&part| self.heap.format_value(part))
+                        .collect();
+                    let string = self.alloc(Object::String(string.into()));
+                    self.stack.truncate(parts);
+                    self.push(Value::Obj(string));
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", self.heap.format_value(value));
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let argument_count = self.read_byte() as usize;
+                    self.call_value(self.peek(argument_count), argument_count)?;
+                }
+                OpCode::Invoke => {
+                    let name = self.read_string();
+                    let argument_count = self.read_byte() as usize;
+                    self.invoke(&name, argument_count)?;
+                }
+                OpCode::SuperInvoke => {
+                    let name = self.read_string();
+                    let argument_count = self.read_byte() as usize;
+                    let superclass = self.pop();
+                    let Some(superclass) = self.class_ref(superclass) else {
+                        return Err(self.runtime_error("Superclass must be a class"));
+                    };
+                    self.invoke_from_class(superclass, &name, argument_count)?;
+                }
+                OpCode::Closure => {
+                    let Value::Obj(function) = self.read_constant() else {
+                        unreachable!("closures are created from function constants");
+                    };
+                    let upvalue_count = match self.heap.get(function) {
+                        Object::Function(function) => function.proto.upvalue_count,
+                        _ => unreachable!("closures are created from function constants"),
+                    };
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().base + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(self.closure(self.frame().closure).upvalues[index]);
+                        }
+                    }
+                    let closure = self.alloc(Object::Closure(Closure { function, upvalues }));
+                    self.push(Value::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self
+                        .frames
+                        .pop()
+                        .unwrap_or_else(|| panic!("Call frame was expected but not found."));
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = self.alloc(Object::Class(Class {
+                        name,
+                        methods: HashMap::new(),
+                    }));
+                    self.push(Value::Obj(class));
+                }
+                OpCode::Inherit => {
+                    let Some(superclass) = self.as_class(self.peek(1)) else {
+                        return Err(self.runtime_error("Superclass must be a class"));
+                    };
+                    let methods = superclass.methods.clone();
+                    let Some(subclass) = self.class_ref(self.peek(0)) else {
+                        return Err(self.runtime_error("Only classes can inherit"));
+                    };
+                    self.heap.update(subclass, |object| {
+                        if let Object::Class(subclass) = object {
+                            subclass.methods.extend(methods);
+                        }
+                    });
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let Some(method) = self.closure_ref(self.peek(0)) else {
+                        return Err(self.runtime_error("Methods must be functions"));
+                    };
+                    let Some(class) = self.class_ref(self.peek(1)) else {
+                        return Err(self.runtime_error("Methods can only be defined on classes"));
+                    };
+                    self.heap.update(class, |object| {
+                        if let Object::Class(class) = object {
+                            class.methods.insert(name, method);
+                        }
+                    });
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<(), LoxError> {
+        let Value::Obj(callee) = callee else {
+            return Err(self.runtime_error("Can only call functions and classes"));
+        };
+        match self.heap.get(callee) {
+            Object::BoundMethod(BoundMethod { receiver, method }) => {
+                let (receiver, method) = (*receiver, *method);
+                let receiver_slot = self.stack.len() - argument_count - 1;
+                self.stack[receiver_slot] = receiver;
+                self.call_closure(method, argument_count)
+            }
+            Object::Class(class) => {
+                let initializer = class.methods.get("init").copied();
+                let instance = self.alloc(Object::Instance(Instance {
+                    class: callee,
+                    fields: HashMap::new(),
+                }));
+                let receiver_slot = self.stack.len() - argument_count - 1;
+                self.stack[receiver_slot] = Value::Obj(instance);
+                match initializer {
+                    Some(initializer) => self.call_closure(initializer, argument_count),
+                    None if argument_count != 0 => Err(self.runtime_error(&format!(
+                        "Expected 0 arguments but got {}",
+                        argument_count
+                    ))),
+                    None => Ok(()),
+                }
+            }
+            Object::Closure(_) => self.call_closure(callee, argument_count),
+            Object::Native(native) => {
+                let (arity, function) = (native.arity, native.function);
+                if argument_count != arity {
+                    return Err(self.runtime_error(&format!(
+                        "Expected {} arguments but got {}",
+                        arity, argument_count
+                    )));
+                }
+                let arguments_start = self.stack.len() - argument_count;
+                let result = function(&self.stack[arguments_start..]).map_err(|e| {
+                    let line = self.current_line();
+                    LoxError::new(&e.message, e.line.or(Some(line)))
+                })?;
+                self.stack.truncate(arguments_start - 1);
+                self.push(result);
+                Ok(())
+            }
+            _ => Err(self.runtime_error("Can only call functions and classes")),
+        }
+    }
+
+    fn call_closure(&mut self, closure: ObjRef, argument_count: usize) -> Result<(), LoxError> {
+        let function = self.closure(closure).function;
+        let Object::Function(function) = self.heap.get(function) else {
+            unreachable!("closures wrap functions");
+        };
+        let (proto, constants) = (Rc::clone(&function.proto), Rc::clone(&function.constants));
+
+        if argument_count != proto.arity {
+            return Err(self.runtime_error(&format!(
+                "Expected {} arguments but got {}",
+                proto.arity, argument_count
+            )));
+        }
+        if self.frames.len() > self.max_call_depth {
+            return Err(self.runtime_error("Stack overflow"));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            proto,
+            constants,
+            ip: 0,
+            base: self.stack.len() - argument_count - 1,
+        });
+        Ok(())
+    }
+
+    fn invoke(&mut self, name: &str, argument_count: usize) -> Result<(), LoxError> {
+        let Some(instance) = self.as_instance(self.peek(argument_count)) else {
+            return Err(self.runtime_error("Only instances have properties"));
+        };
+        // A field holding a callable shadows a method of the same name.
+        if let Some(&field) = instance.fields.get(name) {
+            let receiver_slot = self.stack.len() - argument_count - 1;
+            self.stack[receiver_slot] = field;
+            return self.call_value(field, argument_count);
+        }
+        let class = instance.class;
+        self.invoke_from_class(class, name, argument_count)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: ObjRef,
+        name: &str,
+        argument_count: usize,
+    ) -> Result<(), LoxError> {
+        let Object::Class(class) = self.heap.get(class) else {
+            unreachable!("methods are looked up on classes");
+        };
+        match class.methods.get(name) {
+            Some(&method) => self.call_closure(method, argument_count),
+            None => Err(self.runtime_error(&format!("Undefined property '{}'", name))),
+        }
+    }
+
+    // Replaces the receiver on top of the stack with the named method bound
+    // to it.
+    fn bind_method(&mut self, class: ObjRef, name: &str) -> Result<(), LoxError> {
+        let Object::Class(class) = self.heap.get(class) else {
+            unreachable!("methods are looked up on classes");
+        };
+        let Some(&method) = class.methods.get(name) else {
+            return Err(self.runtime_error(&format!("Undefined property '{}'", name)));
+        };
+        let receiver = self.peek(0);
+        let bound = self.alloc(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let existing = self.open_upvalues.iter().copied().find(|&upvalue| {
+            matches!(self.heap.get(upvalue), Object::Upvalue(Upvalue::Open(open)) if *open == slot)
+        });
+        existing.unwrap_or_else(|| {
+            let upvalue = self.alloc(Object::Upvalue(Upvalue::Open(slot)));
+            self.open_upvalues.push(upvalue);
+            upvalue
+        })
+    }
+
+    // Moves every open upvalue at or above `last` off the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        let heap = &mut self.heap;
+        self.open_upvalues.retain(|&upvalue| {
+            let Object::Upvalue(state) = heap.get_mut(upvalue) else {
+                unreachable!("only upvalues are tracked as open");
+            };
+            match *state {
+                Upvalue::Open(slot) if slot >= last => {
+                    *state = Upvalue::Closed(stack[slot]);
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn add(&mut self) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.pop();
+                self.pop();
+                self.push(Value::Number(a + b));
+            }
+            (a, b) => {
+                let (Some(a), Some(b)) = (self.heap.as_string(a), self.heap.as_string(b)) else {
+                    return Err(self.runtime_error("Operands must be two numbers or two strings"));
+                };
+                let concatenated: Rc<str> = format!("{}{}", a, b).into();
+                self.pop();
+                self.pop();
+                let string = self.alloc(Object::String(concatenated));
+                self.push(Value::Obj(string));
+            }
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, op: fn(f64, f64) -> f64) -> Result<(), LoxError> {
+        let (Value::Number(a), Value::Number(b)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.runtime_error("Operands must be numbers."));
+        };
+        self.pop();
+        self.pop();
+        self.push(Value::Number(op(a, b)));
+        Ok(())
+    }
+
+    fn comparison(&mut self, op: fn(f64, f64) -> bool) -> Result<(), LoxError> {
+        let (Value::Number(a), Value::Number(b)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.runtime_error("Operands must be numbers."));
+        };
+        self.pop();
+        self.pop();
+        self.push(Value::Boolean(op(a, b)));
+        Ok(())
+    }
+
+    fn as_instance(&self, value: Value) -> Option<&Instance> {
+        match value {
+            Value::Obj(reference) => match self.heap.get(reference) {
+                Object::Instance(instance) => Some(instance),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn as_class(&self, value: Value) -> Option<&Class> {
+        match value {
+            Value::Obj(reference) => match self.heap.get(reference) {
+                Object::Class(class) => Some(class),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Bytecode loaded from a file is only checked for its stack depth, not
+    // for the kinds of values on it, so instructions that need a class or a
+    // closure look before they use one.
+    fn class_ref(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Object::Class(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn closure_ref(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Object::Closure(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn closure(&self, reference: ObjRef) -> &Closure {
+        match self.heap.get(reference) {
+            Object::Closure(closure) => closure,
+            _ => unreachable!("call frames always run closures"),
+        }
+    }
+
+    fn frame_upvalue(&mut self) -> ObjRef {
+        let index = self.read_byte() as usize;
+        self.closure(self.frame().closure).upvalues[index]
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.proto.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.proto.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().constants[index]
+    }
+
+    fn read_string(&mut self) -> Rc<str> {
+        let Value::Obj(reference) = self.read_constant() else {
+            unreachable!("names are string constants");
+        };
+        match self.heap.get(reference) {
+            Object::String(s) => Rc::clone(s),
+            _ => unreachable!("names are string constants"),
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames
+            .last()
+            .unwrap_or_else(|| panic!("Call frame was expected but not found."))
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames
+            .last_mut()
+            .unwrap_or_else(|| panic!("Call frame was expected but not found."))
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .unwrap_or_else(|| panic!("Stack value was expected but not found."))
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // The line of the instruction currently executing.
+    fn current_line(&self) -> usize {
+        let frame = self.frame();
+        frame.proto.chunk.lines[frame.ip.saturating_sub(1)]
+    }
+
+    fn runtime_error(&self, message: &str) -> LoxError {
+        LoxError::new(message, Some(self.current_line()))
+    }
+}
+
+fn clock(_arguments: &[Value]) -> Result<Value, LoxError> {
+    Ok(Value::Number(native::seconds_since_epoch()?))
+}
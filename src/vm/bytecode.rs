@@ -0,0 +1,524 @@
+use std::{collections::HashMap, rc::Rc};
+
+use bytes::{Buf, BufMut};
+
+use super::chunk::{Chunk, Constant, FunctionProto, OpCode};
+use crate::error::LoxError;
+
+// Layout of a `.loxc` file, all integers big-endian:
+//
+//   header:    magic "LOXC", format version (u16), payload length (u32),
+//              FNV-1a checksum of the payload (u32)
+//   payload:   the top-level script function
+//   function:  name (string), arity (u8), upvalue count (u16),
+//              constant count (u32) followed by tagged constants,
+//              code length (u32) followed by the code bytes,
+//              line run count (u32) followed by (line u32, length u32) runs
+//   string:    byte length (u32) followed by UTF-8 bytes
+//   constant:  tag (u8) then a number (f64), a string or a nested function
+const MAGIC: &[u8; 4] = b"LOXC";
+// Bumped whenever the layout or the instruction set changes. Version 2 added
+// the interpolation instruction.
+pub const FORMAT_VERSION: u16 = 2;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+const MAX_FUNCTION_NESTING: usize = 256;
+
+pub fn serialize(script: &FunctionProto) -> Vec<u8> {
+    let mut payload = Vec::new();
+    write_function(&mut payload, script);
+
+    let mut buf = Vec::with_capacity(payload.len() + 14);
+    buf.put_slice(MAGIC);
+    buf.put_u16(FORMAT_VERSION);
+    buf.put_u32(payload.len() as u32);
+    buf.put_u32(checksum(&payload));
+    buf.put_slice(&payload);
+    buf
+}
+
+fn write_function(buf: &mut Vec<u8>, proto: &FunctionProto) {
+    write_string(buf, &proto.name);
+    buf.put_u8(proto.arity as u8);
+    buf.put_u16(proto.upvalue_count as u16);
+
+    let chunk = &proto.chunk;
+    buf.put_u32(chunk.constants.len() as u32);
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Number(n) => {
+                buf.put_u8(TAG_NUMBER);
+                buf.put_f64(*n);
+            }
+            Constant::String(s) => {
+                buf.put_u8(TAG_STRING);
+                write_string(buf, s);
+            }
+            Constant::Function(function) => {
+                buf.put_u8(TAG_FUNCTION);
+                write_function(buf, function);
+            }
+        }
+    }
+
+    buf.put_u32(chunk.code.len() as u32);
+    buf.put_slice(&chunk.code);
+
+    // Consecutive bytes nearly always share a line, so store runs.
+    let mut runs: Vec<(usize, u32)> = Vec::new();
+    for &line in &chunk.lines {
+        match runs.last_mut() {
+            Some((last, length)) if *last == line => *length += 1,
+            _ => runs.push((line, 1)),
+        }
+    }
+    buf.put_u32(runs.len() as u32);
+    for (line, length) in runs {
+        buf.put_u32(line as u32);
+        buf.put_u32(length);
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.put_u32(s.len() as u32);
+    buf.put_slice(s.as_bytes());
+}
+
+// Reads a script written by `serialize`, checking that every instruction
+// decodes and refers to constants and jump targets that exist.
+pub fn deserialize(bytes: &[u8]) -> Result<Rc<FunctionProto>, LoxError> {
+    let mut reader = Reader { buf: bytes };
+
+    if reader.remaining() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoxError::new("Not a Lox bytecode file", None));
+    }
+    reader.buf.advance(MAGIC.len());
+
+    let version = reader.u16()?;
+    if version != FORMAT_VERSION {
+        return Err(LoxError::new(
+            &format!(
+                "Unsupported bytecode format version {} (expected {})",
+                version, FORMAT_VERSION
+            ),
+            None,
+        ));
+    }
+
+    let length = reader.u32()? as usize;
+    let expected = reader.u32()?;
+    reader.ensure(length)?;
+    if reader.remaining() > length {
+        return Err(corrupt("unexpected data after the script"));
+    }
+    if checksum(reader.buf) != expected {
+        return Err(corrupt("checksum mismatch"));
+    }
+
+    let script = reader.function(0)?;
+    if reader.remaining() > 0 {
+        return Err(corrupt("unexpected data after the script"));
+    }
+    // The script runs as a closure with no arguments and nothing captured.
+    if script.arity != 0 || script.upvalue_count != 0 {
+        return Err(corrupt("script takes arguments or captures upvalues"));
+    }
+    validate(&script)?;
+    Ok(Rc::new(script))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn function(&mut self, nesting: usize) -> Result<FunctionProto, LoxError> {
+        if nesting > MAX_FUNCTION_NESTING {
+            return Err(corrupt("functions are nested too deeply"));
+        }
+
+        let name = self.string()?;
+        let arity = self.u8()? as usize;
+        let upvalue_count = self.u16()? as usize;
+
+        let constant_count = self.u32()? as usize;
+        let mut constants = Vec::new();
+        for _ in 0..constant_count {
+            let constant = match self.u8()? {
+                TAG_NUMBER => Constant::Number(self.f64()?),
+                TAG_STRING => Constant::String(self.string()?),
+                TAG_FUNCTION => Constant::Function(Rc::new(self.function(nesting + 1)?)),
+                tag => return Err(corrupt(&format!("unknown constant tag {}", tag))),
+            };
+            constants.push(constant);
+        }
+
+        let code_length = self.u32()? as usize;
+        let code = self.bytes(code_length)?.to_vec();
+
+        let run_count = self.u32()? as usize;
+        let mut lines = Vec::with_capacity(code_length);
+        for _ in 0..run_count {
+            let line = self.u32()? as usize;
+            let length = self.u32()? as usize;
+            if lines.len() + length > code_length {
+                return Err(corrupt("line table is longer than the code"));
+            }
+            lines.resize(lines.len() + length, line);
+        }
+        if lines.len() != code_length {
+            return Err(corrupt("line table is shorter than the code"));
+        }
+
+        Ok(FunctionProto {
+            name,
+            arity,
+            upvalue_count,
+            chunk: Chunk {
+                code,
+                lines,
+                constants,
+            },
+        })
+    }
+
+    fn string(&mut self) -> Result<String, LoxError> {
+        let length = self.u32()? as usize;
+        let bytes = self.bytes(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt("string is not valid UTF-8"))
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&[u8], LoxError> {
+        self.ensure(length)?;
+        let (bytes, rest) = self.buf.split_at(length);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoxError> {
+        self.ensure(1)?;
+        Ok(self.buf.get_u8())
+    }
+
+    fn u16(&mut self) -> Result<u16, LoxError> {
+        self.ensure(2)?;
+        Ok(self.buf.get_u16())
+    }
+
+    fn u32(&mut self) -> Result<u32, LoxError> {
+        self.ensure(4)?;
+        Ok(self.buf.get_u32())
+    }
+
+    fn f64(&mut self) -> Result<f64, LoxError> {
+        self.ensure(8)?;
+        Ok(self.buf.get_f64())
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.remaining()
+    }
+
+    fn ensure(&self, length: usize) -> Result<(), LoxError> {
+        if self.remaining() < length {
+            return Err(LoxError::new("Bytecode file is truncated", None));
+        }
+        Ok(())
+    }
+}
+
+// Walks every instruction so the VM never decodes past the end of a chunk,
+// reads a missing constant, jumps outside the code or misuses the stack.
+fn validate(proto: &FunctionProto) -> Result<(), LoxError> {
+    let chunk = &proto.chunk;
+    let code = &chunk.code;
+
+    let mut starts = vec![false; code.len() + 1];
+    let mut instructions = Vec::new();
+    let mut jumps = Vec::new();
+    let mut last_op = None;
+    let mut offset = 0;
+    while offset < code.len() {
+        starts[offset] = true;
+        let op = OpCode::from_byte(code[offset])
+            .ok_or_else(|| corrupt(&format!("unknown opcode {}", code[offset])))?;
+        instructions.push((offset, op));
+        last_op = Some(op);
+        let operand = |width: usize| -> Result<usize, LoxError> {
+            match code.get(offset + 1..offset + 1 + width) {
+                Some(&[byte]) => Ok(byte as usize),
+                Some(&[high, low]) => Ok(u16::from_be_bytes([high, low]) as usize),
+                _ => Err(corrupt("instruction operands run past the end of the code")),
+            }
+        };
+
+        offset += match op {
+            OpCode::Constant => {
+                constant(chunk, operand(2)?)?;
+                3
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                string_constant(chunk, operand(2)?)?;
+                3
+            }
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::Call | OpCode::Interpolate => {
+                operand(1)?;
+                2
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                if operand(1)? >= proto.upvalue_count {
+                    return Err(corrupt("upvalue index out of range"));
+                }
+                2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                jumps.push(offset + 3 + operand(2)?);
+                3
+            }
+            OpCode::Loop => {
+                let target = (offset + 3)
+                    .checked_sub(operand(2)?)
+                    .ok_or_else(|| corrupt("loop jumps before the start of the code"))?;
+                jumps.push(target);
+                3
+            }
+            OpCode::Invoke | OpCode::SuperInvoke => {
+                string_constant(chunk, operand(2)?)?;
+                if code.get(offset + 3).is_none() {
+                    return Err(corrupt("instruction operands run past the end of the code"));
+                }
+                4
+            }
+            OpCode::Closure => {
+                let Constant::Function(function) = constant(chunk, operand(2)?)? else {
+                    return Err(corrupt("closure constant is not a function"));
+                };
+                let upvalues_start = offset + 3;
+                let upvalues_end = upvalues_start + function.upvalue_count * 2;
+                let Some(upvalues) = code.get(upvalues_start..upvalues_end) else {
+                    return Err(corrupt("instruction operands run past the end of the code"));
+                };
+                for upvalue in upvalues.chunks(2) {
+                    let (is_local, index) = (upvalue[0], upvalue[1] as usize);
+                    if is_local > 1 || (is_local == 0 && index >= proto.upvalue_count) {
+                        return Err(corrupt("upvalue index out of range"));
+                    }
+                }
+                upvalues_end - offset
+            }
+            _ => 1,
+        };
+    }
+
+    if jumps
+        .iter()
+        .any(|&target| target > code.len() || !starts[target])
+    {
+        return Err(corrupt("jump target is not an instruction"));
+    }
+    // Execution must end in a return rather than fall off the end.
+    if last_op != Some(OpCode::Return) {
+        return Err(corrupt("code does not end with a return"));
+    }
+    check_stack(proto, &instructions)?;
+
+    for constant in &chunk.constants {
+        if let Constant::Function(function) = constant {
+            validate(function)?;
+        }
+    }
+    Ok(())
+}
+
+// Follows every path through the code tracking the values on the frame's
+// stack, so running it can't pop past the frame, read a local slot that
+// doesn't exist, or leave an upvalue open on a slot that has been popped.
+// Each entry records whether a closure may have captured that slot. Every
+// path into an instruction must agree on how deep the stack is there.
+fn check_stack(proto: &FunctionProto, instructions: &[(usize, OpCode)]) -> Result<(), LoxError> {
+    let code = &proto.chunk.code;
+    let index: HashMap<usize, usize> = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, &(offset, _))| (offset, i))
+        .collect();
+
+    // The callee or receiver sits in slot 0, followed by the arguments.
+    let mut states: Vec<Option<Vec<bool>>> = vec![None; instructions.len()];
+    states[0] = Some(vec![false; proto.arity + 1]);
+    let mut pending = vec![0];
+
+    while let Some(i) = pending.pop() {
+        let (offset, op) = instructions[i];
+        let mut stack = states[i].clone().unwrap_or_default();
+        let byte = |n: usize| code[offset + n] as usize;
+        let u16_at =
+            |n: usize| u16::from_be_bytes([code[offset + n], code[offset + n + 1]]) as usize;
+        let next = instructions.get(i + 1).map(|&(next, _)| next);
+
+        let pop = |stack: &mut Vec<bool>, count: usize| -> Result<(), LoxError> {
+            if count > stack.len() {
+                return Err(corrupt("instruction pops more values than the stack holds"));
+            }
+            if stack.drain(stack.len() - count..).any(|captured| captured) {
+                return Err(corrupt("captured local is popped without closing it"));
+            }
+            Ok(())
+        };
+        let local = |stack: &Vec<bool>, slot: usize| -> Result<(), LoxError> {
+            if slot >= stack.len() {
+                return Err(corrupt("local slot out of range"));
+            }
+            Ok(())
+        };
+
+        let mut successors = Vec::new();
+        match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::GetUpvalue
+            | OpCode::Class => stack.push(false),
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print => pop(&mut stack, 1)?,
+            OpCode::GetLocal => {
+                local(&stack, byte(1))?;
+                stack.push(false);
+            }
+            OpCode::SetLocal => {
+                local(&stack, byte(1))?;
+                pop(&mut stack, 1)?;
+                stack.push(false);
+            }
+            OpCode::SetGlobal
+            | OpCode::SetUpvalue
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate => {
+                pop(&mut stack, 1)?;
+                stack.push(false);
+            }
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit
+            | OpCode::Method => {
+                pop(&mut stack, 2)?;
+                stack.push(false);
+            }
+            OpCode::Interpolate => {
+                pop(&mut stack, byte(1))?;
+                stack.push(false);
+            }
+            OpCode::Call => {
+                pop(&mut stack, byte(1) + 1)?;
+                stack.push(false);
+            }
+            OpCode::Invoke => {
+                pop(&mut stack, byte(3) + 1)?;
+                stack.push(false);
+            }
+            OpCode::SuperInvoke => {
+                pop(&mut stack, byte(3) + 2)?;
+                stack.push(false);
+            }
+            OpCode::Closure => {
+                let Constant::Function(function) = &proto.chunk.constants[u16_at(1)] else {
+                    unreachable!("closure constants were checked to be functions");
+                };
+                for upvalue in 0..function.upvalue_count {
+                    if byte(3 + upvalue * 2) == 1 {
+                        let slot = byte(4 + upvalue * 2);
+                        local(&stack, slot)?;
+                        stack[slot] = true;
+                    }
+                }
+                stack.push(false);
+            }
+            OpCode::CloseUpvalue => {
+                if stack.pop().is_none() {
+                    return Err(corrupt("instruction pops more values than the stack holds"));
+                }
+            }
+            OpCode::Return => {
+                if stack.is_empty() {
+                    return Err(corrupt("instruction pops more values than the stack holds"));
+                }
+                continue;
+            }
+            OpCode::Jump => {
+                successors.push(offset + 3 + u16_at(1));
+            }
+            OpCode::JumpIfFalse => {
+                pop(&mut stack, 1)?;
+                stack.push(false);
+                successors.push(offset + 3 + u16_at(1));
+            }
+            OpCode::Loop => successors.push(offset + 3 - u16_at(1)),
+        }
+        if !matches!(op, OpCode::Jump | OpCode::Loop) {
+            successors.extend(next);
+        }
+
+        for target in successors {
+            let j = index[&target];
+            let merged = match &states[j] {
+                None => stack.clone(),
+                Some(existing) if existing.len() != stack.len() => {
+                    return Err(corrupt(
+                        "paths into an instruction disagree on the stack depth",
+                    ));
+                }
+                Some(existing) => existing.iter().zip(&stack).map(|(a, b)| a | b).collect(),
+            };
+            if states[j].as_ref() != Some(&merged) {
+                states[j] = Some(merged);
+                pending.push(j);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn constant(chunk: &Chunk, index: usize) -> Result<&Constant, LoxError> {
+    chunk
+        .constants
+        .get(index)
+        .ok_or_else(|| corrupt("constant index out of range"))
+}
+
+fn string_constant(chunk: &Chunk, index: usize) -> Result<(), LoxError> {
+    match constant(chunk, index)? {
+        Constant::String(_) => Ok(()),
+        _ => Err(corrupt("name constant is not a string")),
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn corrupt(detail: &str) -> LoxError {
+    LoxError::new(&format!("Bytecode file is corrupt: {}", detail), None)
+}
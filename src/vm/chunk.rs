@@ -0,0 +1,198 @@
+use std::{fmt, rc::Rc};
+
+use crate::utils::pad_number;
+
+// Operands follow the opcode byte: constant indices are two bytes (big-endian),
+// stack slots, upvalue indices, argument counts and interpolation part counts
+// are one byte, and jump offsets are two bytes.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Invoke,
+    SuperInvoke,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+    Interpolate,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 40] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Invoke,
+        OpCode::SuperInvoke,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::Interpolate,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        Self::ALL.get(byte as usize).copied()
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpCode::Constant => write!(fmt, "CONSTANT"),
+            OpCode::Nil => write!(fmt, "NIL"),
+            OpCode::True => write!(fmt, "TRUE"),
+            OpCode::False => write!(fmt, "FALSE"),
+            OpCode::Pop => write!(fmt, "POP"),
+            OpCode::GetLocal => write!(fmt, "GET_LOCAL"),
+            OpCode::SetLocal => write!(fmt, "SET_LOCAL"),
+            OpCode::GetGlobal => write!(fmt, "GET_GLOBAL"),
+            OpCode::DefineGlobal => write!(fmt, "DEFINE_GLOBAL"),
+            OpCode::SetGlobal => write!(fmt, "SET_GLOBAL"),
+            OpCode::GetUpvalue => write!(fmt, "GET_UPVALUE"),
+            OpCode::SetUpvalue => write!(fmt, "SET_UPVALUE"),
+            OpCode::GetProperty => write!(fmt, "GET_PROPERTY"),
+            OpCode::SetProperty => write!(fmt, "SET_PROPERTY"),
+            OpCode::GetSuper => write!(fmt, "GET_SUPER"),
+            OpCode::Equal => write!(fmt, "EQUAL"),
+            OpCode::Greater => write!(fmt, "GREATER"),
+            OpCode::GreaterEqual => write!(fmt, "GREATER_EQUAL"),
+            OpCode::Less => write!(fmt, "LESS"),
+            OpCode::LessEqual => write!(fmt, "LESS_EQUAL"),
+            OpCode::Add => write!(fmt, "ADD"),
+            OpCode::Subtract => write!(fmt, "SUBTRACT"),
+            OpCode::Multiply => write!(fmt, "MULTIPLY"),
+            OpCode::Divide => write!(fmt, "DIVIDE"),
+            OpCode::Not => write!(fmt, "NOT"),
+            OpCode::Negate => write!(fmt, "NEGATE"),
+            OpCode::Print => write!(fmt, "PRINT"),
+            OpCode::Jump => write!(fmt, "JUMP"),
+            OpCode::JumpIfFalse => write!(fmt, "JUMP_IF_FALSE"),
+            OpCode::Loop => write!(fmt, "LOOP"),
+            OpCode::Call => write!(fmt, "CALL"),
+            OpCode::Invoke => write!(fmt, "INVOKE"),
+            OpCode::SuperInvoke => write!(fmt, "SUPER_INVOKE"),
+            OpCode::Closure => write!(fmt, "CLOSURE"),
+            OpCode::CloseUpvalue => write!(fmt, "CLOSE_UPVALUE"),
+            OpCode::Return => write!(fmt, "RETURN"),
+            OpCode::Class => write!(fmt, "CLASS"),
+            OpCode::Inherit => write!(fmt, "INHERIT"),
+            OpCode::Method => write!(fmt, "METHOD"),
+            OpCode::Interpolate => write!(fmt, "INTERPOLATE"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Constant {
+    Number(f64),
+    String(String),
+    Function(Rc<FunctionProto>),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Number(n) => write!(fmt, "{}", pad_number(*n)),
+            Constant::String(s) => write!(fmt, "\"{}\"", s),
+            Constant::Function(function) => write!(fmt, "<fn {}>", function.name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    // The source line of every byte in `code`.
+    pub lines: Vec<usize>,
+    pub constants: Vec<Constant>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+}
+
+// A compiled function: the unit the compiler produces and the VM wraps in a
+// closure at runtime. The top-level script is a function with an empty name.
+#[derive(Debug, Default)]
+pub struct FunctionProto {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
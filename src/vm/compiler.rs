@@ -0,0 +1,738 @@
+use std::rc::Rc;
+
+use super::chunk::{Constant, FunctionProto, OpCode};
+use crate::{
+    ast::{Expr, FunctionDecl, Stmt},
+    error::LoxError,
+    token::{Token, TokenType},
+};
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+struct Upvalue {
+    index: u8,
+    is_local: bool,
+}
+
+struct Loop {
+    scope_depth: usize,
+    break_jumps: Vec<usize>,
+    continue_jumps: Vec<usize>,
+}
+
+// Where a named variable lives once the compiler has resolved it.
+#[derive(Clone, Copy)]
+enum Variable {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+// Compile-time bookkeeping for the function currently being emitted.
+struct FunctionState {
+    proto: FunctionProto,
+    function_type: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: &str, function_type: FunctionType) -> Self {
+        // Slot zero holds the callee, or the receiver inside methods.
+        let receiver = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Script | FunctionType::Function => "",
+        };
+        Self {
+            proto: FunctionProto {
+                name: name.to_string(),
+                ..FunctionProto::default()
+            },
+            function_type,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+// Translates a resolved program into bytecode for the `Vm`.
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    line: usize,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            line: 1,
+        }
+    }
+
+    pub fn compile(mut self, statements: &[Stmt]) -> Result<Rc<FunctionProto>, LoxError> {
+        self.states
+            .push(FunctionState::new("", FunctionType::Script));
+
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+        self.emit_return();
+
+        let state = self
+            .states
+            .pop()
+            .unwrap_or_else(|| panic!("Script function state was expected but not found."));
+        Ok(Rc::new(state.proto))
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), LoxError> {
+        match stmt {
+            Stmt::Print(expr) => {
+                self.expression(expr)?;
+                self.emit_op(OpCode::Print);
+            }
+            Stmt::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit_op(OpCode::Pop);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.line = name.line;
+                self.expression(initializer)?;
+                self.line = name.line;
+                self.define_variable(&name.lexeme)?;
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                for stmt in statements {
+                    self.statement(stmt)?;
+                }
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.statement(then_branch)?;
+
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit_op(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => self.while_statement(condition, body, increment.as_ref())?,
+            Stmt::Break => {
+                self.exit_loop_scopes();
+                let jump = self.emit_jump(OpCode::Jump);
+                self.current_loop().break_jumps.push(jump);
+            }
+            Stmt::Continue => {
+                self.exit_loop_scopes();
+                let jump = self.emit_jump(OpCode::Jump);
+                self.current_loop().continue_jumps.push(jump);
+            }
+            Stmt::Function(declaration) => {
+                self.line = declaration.name.line;
+                if self.current().scope_depth > 0 {
+                    // Declared before the body so the function can call itself.
+                    self.add_local(&declaration.name.lexeme)?;
+                    self.function(declaration, FunctionType::Function)?;
+                } else {
+                    self.function(declaration, FunctionType::Function)?;
+                    let name = self.identifier_constant(&declaration.name.lexeme)?;
+                    self.emit_op_u16(OpCode::DefineGlobal, name);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => self.class_declaration(name, superclass.as_ref(), methods)?,
+            Stmt::Return { keyword, value } => {
+                self.line = keyword.line;
+                match value {
+                    Some(value) => {
+                        self.expression(value)?;
+                        self.line = keyword.line;
+                        self.emit_op(OpCode::Return);
+                    }
+                    None => self.emit_return(),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn while_statement(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: Option<&Expr>,
+    ) -> Result<(), LoxError> {
+        let loop_start = self.chunk_len();
+        self.expression(condition)?;
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+
+        let scope_depth = self.current().scope_depth;
+        self.current_mut().loops.push(Loop {
+            scope_depth,
+            break_jumps: Vec::new(),
+            continue_jumps: Vec::new(),
+        });
+        self.statement(body)?;
+        let current_loop = self
+            .current_mut()
+            .loops
+            .pop()
+            .unwrap_or_else(|| panic!("Loop state was expected but not found."));
+
+        for jump in current_loop.continue_jumps {
+            self.patch_jump(jump)?;
+        }
+        if let Some(increment) = increment {
+            self.expression(increment)?;
+            self.emit_op(OpCode::Pop);
+        }
+        self.emit_loop(loop_start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.emit_op(OpCode::Pop);
+        for jump in current_loop.break_jumps {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    // Discards the locals declared inside the innermost loop before jumping
+    // out of it. The compiler keeps tracking them for the code that follows.
+    fn exit_loop_scopes(&mut self) {
+        let state = self.current();
+        let loop_depth = state
+            .loops
+            .last()
+            .map_or(0, |current_loop| current_loop.scope_depth);
+        let ops: Vec<OpCode> = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > loop_depth)
+            .map(|local| {
+                if local.is_captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect();
+        for op in ops {
+            self.emit_op(op);
+        }
+    }
+
+    fn current_loop(&mut self) -> &mut Loop {
+        self.current_mut().loops.last_mut().unwrap_or_else(|| {
+            panic!("The parser rejects 'break' and 'continue' outside of loops.")
+        })
+    }
+
+    fn class_declaration(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Result<(), LoxError> {
+        self.line = name.line;
+        let name_constant = self.identifier_constant(&name.lexeme)?;
+        let is_local = self.current().scope_depth > 0;
+        if is_local {
+            self.add_local(&name.lexeme)?;
+        }
+        self.emit_op_u16(OpCode::Class, name_constant);
+        if !is_local {
+            self.emit_op_u16(OpCode::DefineGlobal, name_constant);
+        }
+
+        if let Some(superclass) = superclass {
+            self.expression(superclass)?;
+            self.begin_scope();
+            self.add_local("super")?;
+
+            self.named_variable(&name.lexeme)?;
+            self.line = name.line;
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.named_variable(&name.lexeme)?;
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.line = method.name.line;
+            self.function(method, function_type)?;
+            let method_name = self.identifier_constant(&method.name.lexeme)?;
+            self.emit_op_u16(OpCode::Method, method_name);
+        }
+        self.emit_op(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        declaration: &FunctionDecl,
+        function_type: FunctionType,
+    ) -> Result<(), LoxError> {
+        self.states
+            .push(FunctionState::new(&declaration.name.lexeme, function_type));
+        self.begin_scope();
+
+        self.current_mut().proto.arity = declaration.params.len();
+        for param in &declaration.params {
+            self.line = param.line;
+            self.add_local(&param.lexeme)?;
+        }
+        for stmt in &declaration.body {
+            self.statement(stmt)?;
+        }
+        self.emit_return();
+
+        let mut state = self
+            .states
+            .pop()
+            .unwrap_or_else(|| panic!("Function state was expected but not found."));
+        state.proto.upvalue_count = state.upvalues.len();
+
+        self.line = declaration.name.line;
+        let constant = self.make_constant(Constant::Function(Rc::new(state.proto)))?;
+        self.emit_op_u16(OpCode::Closure, constant);
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), LoxError> {
+        match expr {
+            Expr::String(s) => {
+                let constant = self.make_constant(Constant::String(s.to_string()))?;
+                self.emit_op_u16(OpCode::Constant, constant);
+            }
+            Expr::Number(n) => {
+                let constant = self.make_constant(Constant::Number(*n))?;
+                self.emit_op_u16(OpCode::Constant, constant);
+            }
+            Expr::Boolean(true) => self.emit_op(OpCode::True),
+            Expr::Boolean(false) => self.emit_op(OpCode::False),
+            Expr::Nil => self.emit_op(OpCode::Nil),
+            Expr::Unary { operator, right } => {
+                self.expression(right)?;
+                self.line = operator.line;
+                match operator.token_type {
+                    TokenType::Minus => self.emit_op(OpCode::Negate),
+                    _ => self.emit_op(OpCode::Not),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.line = operator.line;
+                match operator.token_type {
+                    TokenType::Plus => self.emit_op(OpCode::Add),
+                    TokenType::Minus => self.emit_op(OpCode::Subtract),
+                    TokenType::Star => self.emit_op(OpCode::Multiply),
+                    TokenType::Slash => self.emit_op(OpCode::Divide),
+                    TokenType::Greater => self.emit_op(OpCode::Greater),
+                    TokenType::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
+                    TokenType::Less => self.emit_op(OpCode::Less),
+                    TokenType::LessEqual => self.emit_op(OpCode::LessEqual),
+                    TokenType::EqualEqual => self.emit_op(OpCode::Equal),
+                    TokenType::BangEqual => {
+                        self.emit_op(OpCode::Equal);
+                        self.emit_op(OpCode::Not);
+                    }
+                    _ => {
+                        self.emit_op(OpCode::Pop);
+                        self.emit_op(OpCode::Pop);
+                        self.emit_op(OpCode::Nil);
+                    }
+                }
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                if operator.token_type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    let end_jump = self.emit_jump(OpCode::Jump);
+                    self.patch_jump(else_jump)?;
+                    self.emit_op(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump)?;
+                } else {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    self.emit_op(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump)?;
+                }
+            }
+            Expr::Grouping(inner) => self.expression(inner)?,
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+                self.emit_op_u8(OpCode::Interpolate, parts.len() as u8);
+            }
+            Expr::Variable { name, .. } => {
+                self.line = name.line;
+                self.named_variable(&name.lexeme)?;
+            }
+            Expr::Assign { name, value, .. } => {
+                self.expression(value)?;
+                self.line = name.line;
+                let variable = self.resolve_variable(&name.lexeme)?;
+                self.emit_set(variable);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call(callee, paren, arguments)?,
+            Expr::Get { object, name } => {
+                self.expression(object)?;
+                self.line = name.line;
+                let name = self.identifier_constant(&name.lexeme)?;
+                self.emit_op_u16(OpCode::GetProperty, name);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.line = name.line;
+                let name = self.identifier_constant(&name.lexeme)?;
+                self.emit_op_u16(OpCode::SetProperty, name);
+            }
+            Expr::This { keyword, .. } => {
+                self.line = keyword.line;
+                self.named_variable("this")?;
+            }
+            Expr::Super {
+                keyword, method, ..
+            } => {
+                self.line = keyword.line;
+                self.named_variable("this")?;
+                self.named_variable("super")?;
+                self.line = method.line;
+                let name = self.identifier_constant(&method.lexeme)?;
+                self.emit_op_u16(OpCode::GetSuper, name);
+            }
+        }
+        Ok(())
+    }
+
+    // Method calls compile to a single invoke instruction so the VM can skip
+    // allocating a bound method.
+    fn call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Result<(), LoxError> {
+        match callee {
+            Expr::Get { object, name } => {
+                self.expression(object)?;
+                self.arguments(arguments)?;
+                self.line = paren.line;
+                let name = self.identifier_constant(&name.lexeme)?;
+                self.emit_op_u16(OpCode::Invoke, name);
+                self.emit_byte(arguments.len() as u8);
+            }
+            Expr::Super {
+                keyword, method, ..
+            } => {
+                self.line = keyword.line;
+                self.named_variable("this")?;
+                self.arguments(arguments)?;
+                self.named_variable("super")?;
+                self.line = paren.line;
+                let name = self.identifier_constant(&method.lexeme)?;
+                self.emit_op_u16(OpCode::SuperInvoke, name);
+                self.emit_byte(arguments.len() as u8);
+            }
+            _ => {
+                self.expression(callee)?;
+                self.arguments(arguments)?;
+                self.line = paren.line;
+                self.emit_op(OpCode::Call);
+                self.emit_byte(arguments.len() as u8);
+            }
+        }
+        Ok(())
+    }
+
+    fn arguments(&mut self, arguments: &[Expr]) -> Result<(), LoxError> {
+        for argument in arguments {
+            self.expression(argument)?;
+        }
+        Ok(())
+    }
+
+    fn named_variable(&mut self, name: &str) -> Result<(), LoxError> {
+        let variable = self.resolve_variable(name)?;
+        self.emit_get(variable);
+        Ok(())
+    }
+
+    fn define_variable(&mut self, name: &str) -> Result<(), LoxError> {
+        if self.current().scope_depth > 0 {
+            // The initializer's value is already in the new local's slot.
+            self.add_local(name)
+        } else {
+            let name = self.identifier_constant(name)?;
+            self.emit_op_u16(OpCode::DefineGlobal, name);
+            Ok(())
+        }
+    }
+
+    fn resolve_variable(&mut self, name: &str) -> Result<Variable, LoxError> {
+        let level = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(level, name) {
+            return Ok(Variable::Local(slot));
+        }
+        if let Some(index) = self.resolve_upvalue(level, name)? {
+            return Ok(Variable::Upvalue(index));
+        }
+        Ok(Variable::Global(self.identifier_constant(name)?))
+    }
+
+    fn resolve_local(&self, level: usize, name: &str) -> Option<u8> {
+        self.states[level]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, level: usize, name: &str) -> Result<Option<u8>, LoxError> {
+        if level == 0 {
+            return Ok(None);
+        }
+        let enclosing = level - 1;
+
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.states[enclosing].locals[slot as usize].is_captured = true;
+            return self.add_upvalue(level, slot, true).map(Some);
+        }
+        if let Some(index) = self.resolve_upvalue(enclosing, name)? {
+            return self.add_upvalue(level, index, false).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn add_upvalue(&mut self, level: usize, index: u8, is_local: bool) -> Result<u8, LoxError> {
+        let upvalues = &mut self.states[level].upvalues;
+        if let Some(existing) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(LoxError::new(
+                "Too many closure variables in function",
+                Some(self.line),
+            ));
+        }
+        upvalues.push(Upvalue { index, is_local });
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn add_local(&mut self, name: &str) -> Result<(), LoxError> {
+        let line = self.line;
+        let state = self.current_mut();
+        if state.locals.len() == MAX_LOCALS {
+            return Err(LoxError::new(
+                "Too many local variables in function",
+                Some(line),
+            ));
+        }
+        let depth = state.scope_depth;
+        state.locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.current_mut().scope_depth -= 1;
+        loop {
+            let state = self.current_mut();
+            let is_captured = match state.locals.last() {
+                Some(local) if local.depth > state.scope_depth => local.is_captured,
+                _ => break,
+            };
+            state.locals.pop();
+            if is_captured {
+                self.emit_op(OpCode::CloseUpvalue);
+            } else {
+                self.emit_op(OpCode::Pop);
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> Result<u16, LoxError> {
+        let existing = self
+            .current()
+            .proto
+            .chunk
+            .constants
+            .iter()
+            .position(|constant| matches!(constant, Constant::String(s) if s == name));
+        match existing {
+            Some(index) => Ok(index as u16),
+            None => self.make_constant(Constant::String(name.to_string())),
+        }
+    }
+
+    fn make_constant(&mut self, constant: Constant) -> Result<u16, LoxError> {
+        let index = self.current_mut().proto.chunk.add_constant(constant);
+        u16::try_from(index)
+            .map_err(|_| LoxError::new("Too many constants in one chunk", Some(self.line)))
+    }
+
+    fn emit_get(&mut self, variable: Variable) {
+        match variable {
+            Variable::Local(slot) => self.emit_op_u8(OpCode::GetLocal, slot),
+            Variable::Upvalue(index) => self.emit_op_u8(OpCode::GetUpvalue, index),
+            Variable::Global(name) => self.emit_op_u16(OpCode::GetGlobal, name),
+        }
+    }
+
+    fn emit_set(&mut self, variable: Variable) {
+        match variable {
+            Variable::Local(slot) => self.emit_op_u8(OpCode::SetLocal, slot),
+            Variable::Upvalue(index) => self.emit_op_u8(OpCode::SetUpvalue, index),
+            Variable::Global(name) => self.emit_op_u16(OpCode::SetGlobal, name),
+        }
+    }
+
+    fn emit_return(&mut self) {
+        if self.current().function_type == FunctionType::Initializer {
+            self.emit_op_u8(OpCode::GetLocal, 0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op_u16(op, u16::MAX);
+        self.chunk_len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> Result<(), LoxError> {
+        let jump = self.chunk_len() - offset - 2;
+        let jump = u16::try_from(jump)
+            .map_err(|_| LoxError::new("Too much code to jump over", Some(self.line)))?;
+        let code = &mut self.current_mut().proto.chunk.code;
+        code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), LoxError> {
+        let offset = self.chunk_len() - loop_start + 3;
+        let offset = u16::try_from(offset)
+            .map_err(|_| LoxError::new("Loop body too large", Some(self.line)))?;
+        self.emit_op_u16(OpCode::Loop, offset);
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_op_u8(&mut self, op: OpCode, operand: u8) {
+        self.emit_byte(op as u8);
+        self.emit_byte(operand);
+    }
+
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16) {
+        self.emit_byte(op as u8);
+        for byte in operand.to_be_bytes() {
+            self.emit_byte(byte);
+        }
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.line;
+        self.current_mut().proto.chunk.write(byte, line);
+    }
+
+    fn chunk_len(&self) -> usize {
+        self.current().proto.chunk.code.len()
+    }
+
+    fn current(&self) -> &FunctionState {
+        self.states
+            .last()
+            .unwrap_or_else(|| panic!("Function state was expected but not found."))
+    }
+
+    fn current_mut(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .unwrap_or_else(|| panic!("Function state was expected but not found."))
+    }
+}
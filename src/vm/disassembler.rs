@@ -0,0 +1,129 @@
+use std::fmt::Write;
+
+use super::chunk::{Chunk, Constant, FunctionProto, OpCode};
+
+// Renders a compiled function and every function nested in its constants.
+// Each instruction is printed as offset, source line, opcode and operands.
+pub fn disassemble(proto: &FunctionProto) -> String {
+    let mut out = String::new();
+    disassemble_function(proto, &mut out);
+    out
+}
+
+fn disassemble_function(proto: &FunctionProto, out: &mut String) {
+    let name = if proto.name.is_empty() {
+        "<script>"
+    } else {
+        &proto.name
+    };
+    writeln!(out, "== {} ==", name).unwrap();
+
+    let chunk = &proto.chunk;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(function) = constant {
+            writeln!(out).unwrap();
+            disassemble_function(function, out);
+        }
+    }
+}
+
+// Writes the instruction at `offset` and returns the offset of the next one.
+fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    write!(out, "{:04} {:>4} ", offset, chunk.lines[offset]).unwrap();
+
+    let byte = chunk.code[offset];
+    let Some(op) = OpCode::from_byte(byte) else {
+        writeln!(out, "UNKNOWN {}", byte).unwrap();
+        return offset + 1;
+    };
+
+    let name = op.to_string();
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = chunk.read_u16(offset + 1);
+            writeln!(
+                out,
+                "{:<16} {:>4} {}",
+                name, index, chunk.constants[index as usize]
+            )
+            .unwrap();
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::Interpolate => {
+            writeln!(out, "{:<16} {:>4}", name, chunk.code[offset + 1]).unwrap();
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let next = offset + 3;
+            let target = if op == OpCode::Loop {
+                next - jump
+            } else {
+                next + jump
+            };
+            writeln!(out, "{:<16} {:>4} -> {:04}", name, offset, target).unwrap();
+            next
+        }
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            let index = chunk.read_u16(offset + 1);
+            let argument_count = chunk.code[offset + 3];
+            writeln!(
+                out,
+                "{:<16} {:>4} {} ({} args)",
+                name, index, chunk.constants[index as usize], argument_count
+            )
+            .unwrap();
+            offset + 4
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1);
+            let constant = &chunk.constants[index as usize];
+            writeln!(out, "{:<16} {:>4} {}", name, index, constant).unwrap();
+
+            let upvalue_count = match constant {
+                Constant::Function(function) => function.upvalue_count,
+                _ => 0,
+            };
+            let mut offset = offset + 3;
+            for _ in 0..upvalue_count {
+                let kind = if chunk.code[offset] == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                writeln!(
+                    out,
+                    "{:04}    | {:<16} {:>4}",
+                    offset,
+                    kind,
+                    chunk.code[offset + 1]
+                )
+                .unwrap();
+                offset += 2;
+            }
+            offset
+        }
+        _ => {
+            writeln!(out, "{}", name).unwrap();
+            offset + 1
+        }
+    }
+}
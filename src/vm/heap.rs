@@ -0,0 +1,239 @@
+use std::fmt;
+
+use super::{
+    object::{Object, Upvalue},
+    value::Value,
+};
+
+// Collect for the first time once this many bytes are live, then whenever
+// the heap has grown to `HEAP_GROW_FACTOR` times what survived the last
+// collection.
+const INITIAL_THRESHOLD: usize = 1024 * 1024;
+const HEAP_GROW_FACTOR: usize = 2;
+
+// A handle to an object owned by the `Heap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GcStats {
+    pub collections: usize,
+    pub bytes_freed: usize,
+    pub peak_heap: usize,
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "[gc] collections: {}, bytes freed: {}, peak heap: {} bytes",
+            self.collections, self.bytes_freed, self.peak_heap
+        )
+    }
+}
+
+// Owns every object the VM allocates and frees the unreachable ones with a
+// mark-and-sweep collector. The VM supplies the roots and decides when it is
+// safe to collect; see `Vm::alloc`.
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    marks: Vec<bool>,
+    free_slots: Vec<usize>,
+    gray: Vec<ObjRef>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            marks: Vec::new(),
+            free_slots: Vec::new(),
+            gray: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        self.bytes_allocated += object.size();
+        self.stats.peak_heap = self.stats.peak_heap.max(self.bytes_allocated);
+
+        match self.free_slots.pop() {
+            Some(index) => {
+                self.objects[index] = Some(object);
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marks.push(false);
+                ObjRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    pub fn get(&self, reference: ObjRef) -> &Object {
+        self.objects[reference.0]
+            .as_ref()
+            .unwrap_or_else(|| panic!("Object {} was used after being freed.", reference.0))
+    }
+
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Object {
+        self.objects[reference.0]
+            .as_mut()
+            .unwrap_or_else(|| panic!("Object {} was used after being freed.", reference.0))
+    }
+
+    // Mutates an object in place, keeping the byte count in step with tables
+    // that grow after the object was allocated.
+    pub fn update<R>(&mut self, reference: ObjRef, f: impl FnOnce(&mut Object) -> R) -> R {
+        let object = self.get_mut(reference);
+        let before = object.size();
+        let result = f(object);
+        let after = object.size();
+
+        self.bytes_allocated = (self.bytes_allocated + after).saturating_sub(before);
+        self.stats.peak_heap = self.stats.peak_heap.max(self.bytes_allocated);
+        result
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_gc
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    pub fn mark_value(&mut self, value: Value) {
+        if let Value::Obj(reference) = value {
+            self.mark_object(reference);
+        }
+    }
+
+    pub fn mark_object(&mut self, reference: ObjRef) {
+        if !self.marks[reference.0] {
+            self.marks[reference.0] = true;
+            self.gray.push(reference);
+        }
+    }
+
+    // Finishes a collection once the VM has marked its roots: traces
+    // everything reachable from them and frees the rest.
+    pub fn collect(&mut self) {
+        while let Some(reference) = self.gray.pop() {
+            self.blacken(reference);
+        }
+
+        let mut live_bytes = 0;
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            let Some(object) = slot else {
+                continue;
+            };
+            if self.marks[index] {
+                self.marks[index] = false;
+                live_bytes += object.size();
+            } else {
+                self.stats.bytes_freed += object.size();
+                *slot = None;
+                self.free_slots.push(index);
+            }
+        }
+
+        self.bytes_allocated = live_bytes;
+        self.next_gc = (live_bytes * HEAP_GROW_FACTOR).max(INITIAL_THRESHOLD);
+        self.stats.collections += 1;
+    }
+
+    fn blacken(&mut self, reference: ObjRef) {
+        let Heap {
+            objects,
+            marks,
+            gray,
+            ..
+        } = self;
+        let mut mark = |value: Value| {
+            if let Value::Obj(child) = value {
+                if !marks[child.0] {
+                    marks[child.0] = true;
+                    gray.push(child);
+                }
+            }
+        };
+
+        let Some(object) = &objects[reference.0] else {
+            return;
+        };
+        match object {
+            Object::String(_) | Object::Native(_) | Object::Upvalue(Upvalue::Open(_)) => {}
+            Object::Function(function) => function.constants.iter().copied().for_each(mark),
+            Object::Closure(closure) => {
+                mark(Value::Obj(closure.function));
+                for &upvalue in &closure.upvalues {
+                    mark(Value::Obj(upvalue));
+                }
+            }
+            Object::Upvalue(Upvalue::Closed(value)) => mark(*value),
+            Object::Class(class) => {
+                for &method in class.methods.values() {
+                    mark(Value::Obj(method));
+                }
+            }
+            Object::Instance(instance) => {
+                mark(Value::Obj(instance.class));
+                instance.fields.values().copied().for_each(mark);
+            }
+            Object::BoundMethod(bound) => {
+                mark(bound.receiver);
+                mark(Value::Obj(bound.method));
+            }
+        }
+    }
+
+    pub fn as_string(&self, value: Value) -> Option<&str> {
+        match value {
+            Value::Obj(reference) => match self.get(reference) {
+                Object::String(s) => Some(s),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Strings compare by content, every other object by identity.
+    pub fn values_equal(&self, a: Value, b: Value) -> bool {
+        match (a, b) {
+            (Value::Obj(l), Value::Obj(r)) if l != r => {
+                matches!((self.get(l), self.get(r)), (Object::String(l), Object::String(r)) if l == r)
+            }
+            _ => a == b,
+        }
+    }
+
+    pub fn format_value(&self, value: Value) -> String {
+        match value {
+            Value::Nil => "nil".to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::Obj(reference) => self.format_object(reference),
+        }
+    }
+
+    fn format_object(&self, reference: ObjRef) -> String {
+        match self.get(reference) {
+            Object::String(s) => s.to_string(),
+            Object::Function(function) => format!("<fn {}>", function.proto.name),
+            Object::Native(_) => "<native fn>".to_string(),
+            Object::Closure(closure) => self.format_object(closure.function),
+            Object::Upvalue(_) => "upvalue".to_string(),
+            Object::Class(class) => class.name.to_string(),
+            Object::Instance(instance) => {
+                format!("{} instance", self.format_object(instance.class))
+            }
+            Object::BoundMethod(bound) => self.format_object(bound.method),
+        }
+    }
+}
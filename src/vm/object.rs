@@ -0,0 +1,76 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{chunk::FunctionProto, heap::ObjRef, value::Value};
+use crate::error::LoxError;
+
+pub type NativeFn = fn(&[Value]) -> Result<Value, LoxError>;
+
+pub enum Object {
+    String(Rc<str>),
+    Function(Function),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+// A loaded function prototype together with its constants converted to
+// runtime values.
+pub struct Function {
+    pub proto: Rc<FunctionProto>,
+    pub constants: Rc<[Value]>,
+}
+
+pub struct Native {
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+// An upvalue points at a stack slot while the variable is still live, and
+// owns the value once the slot is popped.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: Rc<str>,
+    pub methods: HashMap<Rc<str>, ObjRef>,
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<Rc<str>, Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+impl Object {
+    // An estimate of the memory this object keeps alive, used to decide when
+    // to collect garbage.
+    pub fn size(&self) -> usize {
+        let owned = match self {
+            Object::String(s) => s.len(),
+            Object::Function(function) => function.constants.len() * size_of::<Value>(),
+            Object::Native(_) | Object::Upvalue(_) | Object::BoundMethod(_) => 0,
+            Object::Closure(closure) => closure.upvalues.capacity() * size_of::<ObjRef>(),
+            Object::Class(class) => {
+                class.name.len() + class.methods.capacity() * size_of::<(Rc<str>, ObjRef)>()
+            }
+            Object::Instance(instance) => {
+                instance.fields.capacity() * size_of::<(Rc<str>, Value)>()
+            }
+        };
+        size_of::<Object>() + owned
+    }
+}
@@ -0,0 +1,22 @@
+use super::heap::ObjRef;
+
+// A value on the VM stack. Anything larger than a number lives on the heap
+// and is referred to by handle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+impl Value {
+    // `nil` and `false` are falsey; everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fs, process::Command};
+
+// Benchmarks are ignored by default since they are slow in debug builds:
+// cargo test --release --test bench -- --ignored --nocapture
+
+fn nested_blocks_program(globals: usize) -> String {
+    let mut source = String::new();
+    for i in 0..globals {
+        source.push_str(&format!("var global{} = {};\n", i, i));
+    }
+    source.push_str(
+        "var start = clock();
+var i = 0;
+while (i < 2000) {
+    { { { { { { { { var x = i; } } } } } } } }
+    i = i + 1;
+}
+print clock() - start;
+",
+    );
+    source
+}
+
+fn time_nested_blocks(globals: usize) -> f64 {
+    let path = std::env::temp_dir().join(format!("lox_bench_nested_blocks_{}.lox", globals));
+    fs::write(&path, nested_blocks_program(globals)).expect("Failed to write benchmark file");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .arg("run")
+        .arg(&path)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&path).ok();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .parse::<f64>()
+        .expect("Benchmark did not print its elapsed time")
+}
+
+#[test]
+#[ignore]
+fn bench_nested_blocks_independent_of_global_scope_size() {
+    let small = time_nested_blocks(10);
+    let large = time_nested_blocks(10_000);
+    println!("nested blocks with 10 globals:     {:.4}s", small);
+    println!("nested blocks with 10,000 globals: {:.4}s", large);
+
+    // Entering a block only links a new scope to its parent, so a thousandfold
+    // larger global scope must not make the loop meaningfully slower.
+    assert!(large < small * 3.0 + 0.05);
+}
@@ -0,0 +1,245 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn compile(source: &str, name: &str) -> PathBuf {
+    let output = env::temp_dir().join(format!("lox_compile_{}.loxc", name));
+    let status = Command::new("./target/debug/lox-rs")
+        .arg("compile")
+        .arg(source)
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("Failed to execute process");
+    assert_eq!(status.code(), Some(0));
+    output
+}
+
+// Writes a script whose top-level code is `code`, with no constants, in the
+// format version the binary writes, and runs it.
+fn run_handwritten(code: &[u8], name: &str) -> std::process::Output {
+    run_handwritten_script(0, &[], code, name)
+}
+
+// Like `run_handwritten`, but with the given upvalue count and string
+// constants.
+fn run_handwritten_script(
+    upvalue_count: u16,
+    strings: &[&str],
+    code: &[u8],
+    name: &str,
+) -> std::process::Output {
+    let compiled = compile("tests/run/lox_files/closure.lox", name);
+    let version = fs::read(&compiled).expect("Failed to read bytecode")[4..6].to_vec();
+
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&0u32.to_be_bytes()); // name
+    payload.push(0); // arity
+    payload.extend_from_slice(&upvalue_count.to_be_bytes());
+    payload.extend_from_slice(&(strings.len() as u32).to_be_bytes());
+    for string in strings {
+        payload.push(1); // string tag
+        payload.extend_from_slice(&(string.len() as u32).to_be_bytes());
+        payload.extend_from_slice(string.as_bytes());
+    }
+    payload.extend_from_slice(&(code.len() as u32).to_be_bytes());
+    payload.extend_from_slice(code);
+    payload.extend_from_slice(&1u32.to_be_bytes()); // one line run
+    payload.extend_from_slice(&1u32.to_be_bytes());
+    payload.extend_from_slice(&(code.len() as u32).to_be_bytes());
+
+    let checksum = payload.iter().fold(0x811c_9dc5u32, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    let mut bytes = b"LOXC".to_vec();
+    bytes.extend_from_slice(&version);
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&checksum.to_be_bytes());
+    bytes.extend_from_slice(&payload);
+    fs::write(&compiled, &bytes).expect("Failed to write bytecode");
+
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&compiled)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&compiled).ok();
+    output
+}
+
+const NIL: u8 = 1;
+const POP: u8 = 4;
+const GET_LOCAL: u8 = 5;
+const GET_UPVALUE: u8 = 10;
+const GET_SUPER: u8 = 14;
+const ADD: u8 = 20;
+const RETURN: u8 = 35;
+const METHOD: u8 = 38;
+
+#[test]
+fn test_compile_and_run() {
+    let bytecode = compile("tests/run/lox_files/class.lox", "class");
+
+    let from_source = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/class.lox")
+        .output()
+        .expect("Failed to execute process");
+    let from_bytecode = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&bytecode)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&bytecode).ok();
+
+    assert_eq!(from_bytecode.stdout, from_source.stdout);
+    assert_eq!(from_bytecode.status.code(), Some(0));
+}
+
+#[test]
+fn test_compile_runtime_error_keeps_lines() {
+    let bytecode = compile(
+        "tests/run/lox_files/undefined_property.lox",
+        "runtime_error",
+    );
+
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&bytecode)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&bytecode).ok();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Undefined property 'missing'"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_compile_rejects_truncated_file() {
+    let bytecode = compile("tests/run/lox_files/closure.lox", "truncated");
+    let bytes = fs::read(&bytecode).expect("Failed to read bytecode");
+    fs::write(&bytecode, &bytes[..bytes.len() / 2]).expect("Failed to write bytecode");
+
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&bytecode)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&bytecode).ok();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "Error: Bytecode file is truncated");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_other_version() {
+    let bytecode = compile("tests/run/lox_files/closure.lox", "version");
+    let mut bytes = fs::read(&bytecode).expect("Failed to read bytecode");
+    bytes[4..6].copy_from_slice(&99u16.to_be_bytes());
+    fs::write(&bytecode, &bytes).expect("Failed to write bytecode");
+
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&bytecode)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&bytecode).ok();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "Error: Unsupported bytecode format version 99 (expected 2)"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_source_file() {
+    let path = env::temp_dir().join("lox_compile_not_bytecode.loxc");
+    fs::write(&path, "print 1;").expect("Failed to write file");
+
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg(&path)
+        .output()
+        .expect("Failed to execute process");
+    fs::remove_file(&path).ok();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "Error: Not a Lox bytecode file");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_stack_underflow() {
+    let output = run_handwritten(&[POP, POP, RETURN], "underflow");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "Error: Bytecode file is corrupt: instruction pops more values than the stack holds"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_binary_op_on_empty_stack() {
+    let output = run_handwritten(&[ADD, RETURN], "bare_add");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "Error: Bytecode file is corrupt: instruction pops more values than the stack holds"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_local_out_of_range() {
+    let output = run_handwritten(&[GET_LOCAL, 200, RETURN], "local_range");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "Error: Bytecode file is corrupt: local slot out of range"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_rejects_script_with_upvalues() {
+    let output = run_handwritten_script(1, &[], &[GET_UPVALUE, 0, RETURN], "script_upvalue");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "Error: Bytecode file is corrupt: script takes arguments or captures upvalues"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_compile_super_of_non_class_is_runtime_error() {
+    let output = run_handwritten_script(0, &["x"], &[NIL, GET_SUPER, 0, 0, RETURN], "bad_super");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 1] Error: Superclass must be a class");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_compile_method_on_non_class_is_runtime_error() {
+    let output = run_handwritten_script(
+        0,
+        &["x"],
+        &[NIL, NIL, METHOD, 0, 0, POP, RETURN],
+        "bad_method",
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 1] Error: Methods must be functions");
+    assert_eq!(output.status.code(), Some(70));
+}
@@ -0,0 +1,79 @@
+use std::process::Command;
+
+#[test]
+fn test_disassemble_global() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("disassemble")
+        .arg("tests/disassemble/lox_files/global.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "== <script> ==
+0000    1 CONSTANT            0 1.0
+0003    1 DEFINE_GLOBAL       1 \"a\"
+0006    2 GET_GLOBAL          1 \"a\"
+0009    2 CONSTANT            2 2.0
+0012    2 LESS
+0013    2 JUMP_IF_FALSE      13 -> 0028
+0016    2 POP
+0017    3 GET_GLOBAL          1 \"a\"
+0020    3 CONSTANT            3 3.0
+0023    3 ADD
+0024    3 PRINT
+0025    3 JUMP               25 -> 0029
+0028    3 POP
+0029    3 NIL
+0030    3 RETURN"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_disassemble_closure() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("disassemble")
+        .arg("tests/disassemble/lox_files/closure.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "== <script> ==
+0000    1 CLOSURE             0 <fn outer>
+0003    1 DEFINE_GLOBAL       1 \"outer\"
+0006    1 NIL
+0007    1 RETURN
+
+== outer ==
+0000    2 CONSTANT            0 \"x\"
+0003    3 CLOSURE             1 <fn inner>
+0006    | local               1
+0008    6 GET_LOCAL           2
+0010    6 RETURN
+0011    6 NIL
+0012    6 RETURN
+
+== inner ==
+0000    4 GET_UPVALUE         0
+0002    4 RETURN
+0003    4 NIL
+0004    4 RETURN"
+    );
+}
+
+#[test]
+fn test_disassemble_syntax_error() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("disassemble")
+        .arg("tests/disassemble/lox_files/syntax_error.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 1] Error: Unexpected token: ';'");
+    assert_eq!(output.status.code(), Some(65));
+}
@@ -0,0 +1,80 @@
+use std::process::{Command, Output};
+
+fn run_with_stats(path: &str, use_vm: bool) -> Output {
+    let mut command = Command::new("./target/debug/lox-rs");
+    command.arg("run").arg("--gc-stats").arg(path);
+    if use_vm {
+        command.arg("--vm");
+    }
+    command.output().expect("Failed to execute process")
+}
+
+// Pulls the numbers out of "[gc] collections: 1, bytes freed: 2, peak heap: 3 bytes".
+fn parse_stats(stderr: &str) -> (usize, usize, usize) {
+    let numbers: Vec<usize> = stderr
+        .trim()
+        .strip_prefix("[gc] ")
+        .expect("Missing gc stats line")
+        .split(", ")
+        .map(|part| {
+            part.rsplit(": ")
+                .next()
+                .and_then(|value| value.trim_end_matches(" bytes").parse().ok())
+                .expect("Malformed gc stats")
+        })
+        .collect();
+    (numbers[0], numbers[1], numbers[2])
+}
+
+// Every iteration leaves behind two instances that refer to themselves and
+// each other; the heap only stays small if those cycles are freed.
+fn assert_collects_cycles(output: &Output, expected_stdout: &str) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), expected_stdout);
+    assert_eq!(output.status.code(), Some(0));
+
+    let (collections, bytes_freed, peak_heap) =
+        parse_stats(&String::from_utf8_lossy(&output.stderr));
+    assert!(collections > 0);
+    assert!(bytes_freed > peak_heap);
+    assert!(peak_heap < 4 * 1024 * 1024);
+}
+
+// The collector runs while globals, closures and bound methods are still in
+// use; they must survive it.
+fn assert_keeps_reachable_objects(output: &Output) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "keep\n51\namxy\namxy");
+    let (collections, _, _) = parse_stats(&String::from_utf8_lossy(&output.stderr));
+    assert!(collections > 0);
+}
+
+#[test]
+fn test_gc_collects_cycles() {
+    let output = run_with_stats("tests/gc/lox_files/cycles.lox", true);
+    assert_collects_cycles(&output, "20000");
+}
+
+#[test]
+fn test_gc_keeps_reachable_objects() {
+    let output = run_with_stats("tests/gc/lox_files/survivors.lox", true);
+    assert_keeps_reachable_objects(&output);
+}
+
+#[test]
+fn test_gc_collects_cycles_tree_walker() {
+    let output = run_with_stats("tests/gc/lox_files/cycles.lox", false);
+    assert_collects_cycles(&output, "20000");
+}
+
+#[test]
+fn test_gc_collects_closure_cycles_tree_walker() {
+    let output = run_with_stats("tests/gc/lox_files/closure_cycles.lox", false);
+    assert_collects_cycles(&output, "20000");
+}
+
+#[test]
+fn test_gc_keeps_reachable_objects_tree_walker() {
+    let output = run_with_stats("tests/gc/lox_files/survivors.lox", false);
+    assert_keeps_reachable_objects(&output);
+}
@@ -23,3 +23,19 @@ fn test_parse_number() {
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert_eq!(stdout.trim(), "(+ 2.0 3.0)");
 }
+
+#[test]
+fn test_parse_optimized_folds_literals() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("parse")
+        .arg("--optimized")
+        .arg("tests/parse/lox_files/fold.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "9.0\nfoobar\ntrue\n3.0\ntrue\ntrue\n(- a 1.0)\n(* x 4.0)\n(/ 1.0 0.0)\nx"
+    );
+}
@@ -0,0 +1,96 @@
+use std::process::Command;
+
+#[test]
+fn test_resolve_scope() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/scope.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "global\nglobal\nblock");
+}
+
+#[test]
+fn test_resolve_own_initializer() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/own_initializer.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Can't read local variable in its own initializer"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_resolve_redeclare_local() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/redeclare_local.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Already a variable with this name in this scope"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_resolve_top_level_return() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/top_level_return.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 2] Error: Can't return from top-level code"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_resolve_initializer_return() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/initializer_return.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Can't return a value from an initializer"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_resolve_this_outside_class() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/resolve/lox_files/this_outside_class.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 2] Error: Can't use 'this' outside of a class"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
@@ -0,0 +1,499 @@
+use std::process::Command;
+
+#[test]
+fn test_run_block_scope() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/test.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "quz89\n1395");
+}
+
+#[test]
+fn test_run_if_else() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/if_else.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "big\nthree");
+}
+
+#[test]
+fn test_run_while() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/while.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "0\n1\n2");
+}
+
+#[test]
+fn test_run_for() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/for.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "0\n1\n2\n120");
+}
+
+#[test]
+fn test_run_logical() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/logical.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "hi\nyes\nfalse\n3\nfalse\n0\nprecedence");
+}
+
+#[test]
+fn test_run_function() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/function.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "3\n55\n2\nnil\n<fn add>");
+}
+
+#[test]
+fn test_run_function_arity() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/arity.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 4] Error: Expected 2 arguments but got 1"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_closure() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/closure.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "1\n2\n1\nupdated\nchanged");
+}
+
+#[test]
+fn test_run_class() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/class.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "3\n11\n13\norigin\nPoint\nPoint instance\ntrue\nfalse\ntrue\n0"
+    );
+}
+
+#[test]
+fn test_run_undefined_property() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/undefined_property.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Undefined property 'missing'"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_inheritance() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/inheritance.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "Rex makes a sound: woof\nanimal Rex\n0\nBit makes a sound: woof!"
+    );
+}
+
+#[test]
+fn test_run_inherit_non_class() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/inherit_non_class.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 2] Error: Superclass must be a class");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_inherit_self() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/inherit_self.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 1] Error: A class can't inherit from itself"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_super_without_superclass() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/super_without_superclass.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Can't use 'super' in a class with no superclass"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_equality() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/equality.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "true\nfalse\ntrue\nfalse\ntrue\nfalse");
+}
+
+#[test]
+fn test_run_native_clock() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/clock.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "true\ntrue\n<native fn>\ntrue");
+}
+
+#[test]
+fn test_run_native_arity() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/native_arity.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 1] Error: Expected 0 arguments but got 1"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_break_continue() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/break_continue.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "0\n2\n3\n4\n0\n10\n1\n11\n3\nnil");
+}
+
+#[test]
+fn test_run_break_outside_loop() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/break_outside_loop.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 2] Error: Can't use 'break' outside of a loop"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_string_equality() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/string_equality.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "true\ntrue\ntrue\ntrue\ntrue");
+}
+
+#[test]
+fn test_run_folded_runtime_error() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/folded_runtime_error.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout.trim(), "9");
+    assert_eq!(stderr.trim(), "[line 2] Error: Operands must be numbers.");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_local_slots() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/local_slots.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "2\n11\n6\nouter\nouter\ninner\n0\n11");
+}
+
+#[test]
+fn test_run_deep_recursion() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/deep_recursion.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "3000");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_run_stack_overflow() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/stack_overflow.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 2] Error: Stack overflow");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_deep_nesting() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/deep_nesting.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Maximum nesting depth of 1000 exceeded"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_long_chain() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/long_chain.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "5000\ndot\ntrue");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_run_too_long_chain() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/too_long_chain.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Maximum expression length of 5000 exceeded"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_max_call_depth() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/max_call_depth.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout.trim(), "0");
+    assert_eq!(stderr.trim(), "[line 2] Error: Stack overflow");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_run_lowered_call_depth() {
+    for backend in [None, Some("--vm")] {
+        let output = Command::new("./target/debug/lox-rs")
+            .arg("run")
+            .args(backend)
+            .args(["--max-call-depth", "100"])
+            .arg("tests/run/lox_files/call_depth.lox")
+            .output()
+            .expect("Failed to execute process");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stdout.trim(), "0", "{:?}", backend);
+        assert_eq!(
+            stderr.trim(),
+            "[line 2] Error: Stack overflow",
+            "{:?}",
+            backend
+        );
+        assert_eq!(output.status.code(), Some(70), "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_run_lowered_max_depth() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .args(["--max-depth", "10"])
+        .arg("tests/run/lox_files/deep_nesting.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Maximum nesting depth of 10 exceeded"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_run_raised_max_depth() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .args(["--max-depth", "2000"])
+        .arg("tests/run/lox_files/raised_depth.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "1");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_run_invalid_limit() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .args(["--max-call-depth", "deep"])
+        .arg("tests/run/lox_files/call_depth.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "--max-call-depth takes a number");
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn test_run_string_escapes() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/string_escapes.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "one\ntwo\nsay \"hi\"\t\\o/\ncafé 😀\ntrue");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_run_string_interpolation() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/interpolation.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.trim(),
+        "total: 3.5\n12.5\nnested inner 2 {quoted} done\npoint (3, 4) is a Point nil true\nliteral ${a}"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_run_interpolation_missing_brace() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/run/lox_files/interpolation_missing_brace.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 2] Error: Expected '}' after interpolated expression"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
@@ -93,3 +93,175 @@ fn test_scanning_identifier_handling() {
         "IDENTIFIER foo null\nIDENTIFIER bar null\nIDENTIFIER _hello null\nIDENTIFIER _123_hello null\nNUMBER 6 6.0\nIDENTIFIER az null\nNUMBER 6 6.0\nIDENTIFIER ar null\nIDENTIFIER f00 null\nIDENTIFIER fo null\nAND and null\nLEFT_PAREN ( null\nRIGHT_PAREN ) null\nLESS < null\nGREATER > null\nLESS < null\nGREATER > null\nEOF  null";
     assert_eq!(stdout.trim(), expected_output);
 }
+
+#[test]
+fn test_scanning_keyword_prefixed_identifiers() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/keyword_prefixes.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "VAR var null\nIDENTIFIER printer null\nEQUAL = null\nIDENTIFIER variance null\nPLUS + null\nIDENTIFIER varName null\nSEMICOLON ; null\nPRINT print null\nIDENTIFIER printer null\nSEMICOLON ; null\nIDENTIFIER printer null\nLEFT_PAREN ( null\nIDENTIFIER varied null\nRIGHT_PAREN ) null\nSEMICOLON ; null\nIDENTIFIER andy null\nIDENTIFIER or_else null\nIDENTIFIER classy null\nIDENTIFIER elsewhere null\nIDENTIFIER falsey null\nIDENTIFIER forest null\nIDENTIFIER funky null\nIDENTIFIER iffy null\nIDENTIFIER nilly null\nIDENTIFIER returned null\nIDENTIFIER superb null\nIDENTIFIER thisOne null\nIDENTIFIER trueish null\nIDENTIFIER whiles null\nIDENTIFIER breaks null\nIDENTIFIER continued null\nPRINT print null\nLEFT_PAREN ( null\nIDENTIFIER var_ null\nRIGHT_PAREN ) null\nSEMICOLON ; null\nEOF  null";
+    assert_eq!(stdout.trim(), expected_output);
+}
+
+#[test]
+fn test_scanning_keyword_prefixed_identifiers_run() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("run")
+        .arg("tests/scanning/lox_files/keyword_prefixes_run.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "p!\n2");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_scanning_spans() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("--spans")
+        .arg("tests/scanning/lox_files/spans.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "VAR var null 1:1 0..3\nIDENTIFIER café null 1:5 4..9\nEQUAL = null 1:10 10..11\nSTRING \"naïve\" naïve 1:12 12..20\nSEMICOLON ; null 1:19 20..21\nPRINT print null 2:3 24..29\nIDENTIFIER café null 2:9 30..35\nPLUS + null 2:14 36..37\nSTRING \"ü\" ü 2:16 38..42\nSEMICOLON ; null 2:19 42..43\nIDENTIFIER x null 4:1 53..54\nBANG_EQUAL != null 4:3 55..57\nNUMBER 1.5 1.5 4:6 58..61\nSEMICOLON ; null 4:9 61..62\nEOF  null 5:1 63..63";
+    assert_eq!(stdout.trim(), expected_output);
+}
+
+#[test]
+fn test_scanning_string_escapes() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/escapes.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "STRING \"tab\\there\" tab\there\nSTRING \"quote\\\"s\" quote\"s\nSTRING \"back\\\\slash\" back\\slash\nSTRING \"\\u{e9}t\\u{E9}\" été\nEOF  null";
+    assert_eq!(stdout.trim(), expected_output);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_scanning_multiline_string() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("--spans")
+        .arg("tests/scanning/lox_files/multiline_string.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "VAR var null 1:1 0..3\nIDENTIFIER s null 1:5 4..5\nEQUAL = null 1:7 6..7\nSTRING \"ab\ncd\" ab\ncd 1:9 8..15\nSEMICOLON ; null 2:4 15..16\nEOF  null 3:1 17..17";
+    assert_eq!(stdout.trim(), expected_output);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_scanning_unterminated_multiline_string() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("--spans")
+        .arg("tests/scanning/lox_files/unterminated_multiline_string.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.trim(), "[line 3:5] Error: Unterminated string.");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_scanning_error_spans() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("--spans")
+        .arg("tests/scanning/lox_files/error_spans.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected_errors = "[line 1:12] Error: Unexpected character: $\n[line 2:6] Error: Invalid escape sequence: \\q";
+    assert_eq!(stderr.trim(), expected_errors);
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_scanning_string_escape_errors() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/escape_error.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected_errors = "[line 2] Error: Invalid escape sequence: \\q\n[line 3] Error: Invalid code point in escape: \\u{D800}";
+    assert_eq!(stderr.trim(), expected_errors);
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_scanning_string_interpolation() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/interpolation.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "INTERPOLATION \"a ${ a \nIDENTIFIER x null\nINTERPOLATION_MIDDLE } b ${  b \nINTERPOLATION \"c${ c\nIDENTIFIER y null\nINTERPOLATION_END }\" \nINTERPOLATION_END } d\"  d\nINTERPOLATION \"${ \nSTRING \"}\" }\nINTERPOLATION_END }\" \nEOF  null";
+    assert_eq!(stdout.trim(), expected_output);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_scanning_unterminated_interpolation() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/unterminated_interpolation.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Unterminated string interpolation."
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_scanning_block_comments() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("--spans")
+        .arg("tests/scanning/lox_files/block_comments.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_output = "IDENTIFIER a null 1:1 0..1\nIDENTIFIER b null 2:9 17..18\nIDENTIFIER c null 4:23 59..60\nSLASH / null 4:30 66..67\nIDENTIFIER d null 4:32 68..69\nIDENTIFIER e null 5:17 86..87\nSTAR * null 5:19 88..89\nSLASH / null 5:20 89..90\nEOF  null 6:1 91..91";
+    assert_eq!(stdout.trim(), expected_output);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_scanning_unterminated_block_comment() {
+    let output = Command::new("./target/debug/lox-rs")
+        .arg("tokenize")
+        .arg("tests/scanning/lox_files/unterminated_block_comment.lox")
+        .output()
+        .expect("Failed to execute process");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout.trim(), "IDENTIFIER a null\nEOF  null");
+    assert_eq!(stderr.trim(), "[line 2] Error: Unterminated block comment.");
+    assert_eq!(output.status.code(), Some(65));
+}
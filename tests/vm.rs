@@ -0,0 +1,77 @@
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+fn run(path: &str, use_vm: bool) -> Output {
+    let mut command = Command::new("./target/debug/lox-rs");
+    command.arg("run").arg(path);
+    if use_vm {
+        command.arg("--vm");
+    }
+    command.output().expect("Failed to execute process")
+}
+
+// Runs every program in `dir` on both backends and checks that stdout, stderr
+// and the exit code agree.
+fn assert_backends_agree(dir: &str) {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("Failed to read test directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No programs found in {}", dir);
+
+    for path in paths {
+        let path = path.to_str().expect("Test paths are valid UTF-8");
+        let tree_walker = run(path, false);
+        let vm = run(path, true);
+
+        assert_eq!(
+            String::from_utf8_lossy(&vm.stdout),
+            String::from_utf8_lossy(&tree_walker.stdout),
+            "stdout differs for {}",
+            path
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&vm.stderr),
+            String::from_utf8_lossy(&tree_walker.stderr),
+            "stderr differs for {}",
+            path
+        );
+        assert_eq!(
+            vm.status.code(),
+            tree_walker.status.code(),
+            "exit code differs for {}",
+            path
+        );
+    }
+}
+
+#[test]
+fn test_vm_matches_tree_walker_on_run_programs() {
+    assert_backends_agree("tests/run/lox_files");
+}
+
+#[test]
+fn test_vm_matches_tree_walker_on_resolve_programs() {
+    assert_backends_agree("tests/resolve/lox_files");
+}
+
+#[test]
+fn test_vm_matches_tree_walker_on_closures_and_classes() {
+    assert_backends_agree("tests/vm/lox_files");
+}
+
+#[test]
+fn test_vm_runtime_error() {
+    let output = run("tests/run/lox_files/undefined_property.lox", true);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim(),
+        "[line 3] Error: Undefined property 'missing'"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}